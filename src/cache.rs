@@ -0,0 +1,319 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use reqwest::{
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    Method, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{client::Response, config, SecretString};
+
+const GH_CACHE_DIR: &str = "GH_CACHE_DIR";
+const XDG_CACHE_HOME: &str = "XDG_CACHE_HOME";
+const LOCAL_APP_DATA: &str = "LocalAppData";
+
+// Mirrors gh's config.CacheDir.
+pub(crate) fn cache_dir() -> Option<PathBuf> {
+    config::gh_dir(GH_CACHE_DIR, XDG_CACHE_HOME, LOCAL_APP_DATA, ".cache")
+}
+
+// Responses kept on disk, one file per request, for as long as the ttl.
+#[derive(Debug, Clone)]
+pub(crate) struct Cache<'a> {
+    pub(crate) dir: &'a Path,
+    pub(crate) ttl: Duration,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Cached {
+    pub(crate) response: Response,
+    pub(crate) fresh: bool, // still within the ttl
+}
+
+// What's written ahead of the body in each file.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+}
+
+impl Cache<'_> {
+    // Like go-gh, only requests that read data are cached, which includes every
+    // GraphQL request as they're all POSTs.
+    pub(crate) fn is_cacheable(method: &Method, url: &Url) -> bool {
+        match *method {
+            Method::GET | Method::HEAD => true,
+            Method::POST => url.path().ends_with("/graphql"),
+            _ => false,
+        }
+    }
+
+    // Hashing in the token keeps accounts from seeing each other's responses, without
+    // the token itself ending up on disk.
+    pub(crate) fn key(
+        method: &Method,
+        url: &Url,
+        token: &SecretString,
+        body: Option<&[u8]>,
+    ) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(format!("{method}:{url}:"));
+        hasher.update(token.expose());
+        hasher.update(":");
+        hasher.update(body.unwrap_or_default());
+
+        format!("{:x}", hasher.finalize())
+    }
+
+    // Stale responses are still returned, as they can be revalidated.
+    pub(crate) fn get(&self, key: &str) -> Option<Cached> {
+        let path = self.dir.join(key);
+        let modified = path
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()?;
+        let fresh = SystemTime::now() <= modified + self.ttl;
+
+        let contents = std::fs::read(path).ok()?;
+        let split = contents.iter().position(|byte| *byte == b'\n')?;
+        let entry = serde_json::from_slice::<Entry>(&contents[..split]).ok()?;
+
+        let response = Response {
+            url: entry.url,
+            status: StatusCode::from_u16(entry.status).ok()?,
+            headers: entry
+                .headers
+                .into_iter()
+                .filter_map(|(name, value)| {
+                    Some((
+                        HeaderName::try_from(name).ok()?,
+                        HeaderValue::try_from(value).ok()?,
+                    ))
+                })
+                .collect(),
+            body: contents[split + 1..].to_vec(),
+        };
+
+        Some(Cached { response, fresh })
+    }
+
+    // Makes the request conditional on the stale response having changed, so that
+    // the API can answer 304 Not Modified, which doesn't count against the rate limit.
+    // Returns whether the response had anything to check against.
+    pub(crate) fn revalidate(headers: &mut HeaderMap, stale: &Response) -> bool {
+        let mut revalidated = false;
+        for (validator, condition) in [(ETAG, IF_NONE_MATCH), (LAST_MODIFIED, IF_MODIFIED_SINCE)] {
+            if let Some(value) = stale.headers.get(validator) {
+                headers.insert(condition, value.clone());
+                revalidated = true;
+            }
+        }
+
+        revalidated
+    }
+
+    // Caching is best effort, so failing to write just means the next request goes
+    // to the API again.
+    pub(crate) fn put(&self, key: &str, response: &Response) {
+        let entry = Entry {
+            url: response.url.clone(),
+            status: response.status.as_u16(),
+            headers: headers(&response.headers),
+        };
+
+        let _ = create_dir(self.dir).and_then(|()| {
+            // Written aside and renamed so that readers never see half a file. The temp
+            // file is only readable by the user, as responses may be from private repos.
+            let mut file = tempfile::NamedTempFile::new_in(self.dir)?;
+            serde_json::to_writer(&mut file, &entry)?;
+            file.write_all(b"\n")?;
+            file.write_all(&response.body)?;
+            file.persist(self.dir.join(key))?;
+            Ok(())
+        });
+    }
+}
+
+// Like gh, the cache is kept from other users.
+fn create_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+fn headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_owned())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(method: Method, url: &str, token: &str, body: &str) -> String {
+        Cache::key(
+            &method,
+            &url.parse().unwrap(),
+            &token.into(),
+            Some(body.as_bytes()),
+        )
+    }
+
+    #[test]
+    fn cache_dir_prefers_gh_cache_dir() {
+        temp_env::with_vars(
+            [
+                (GH_CACHE_DIR, Some("/gh-cache-dir")),
+                (XDG_CACHE_HOME, Some("/xdg-cache-home")),
+            ],
+            || assert_eq!(cache_dir(), Some(PathBuf::from("/gh-cache-dir"))),
+        );
+    }
+
+    #[test]
+    fn cache_dir_uses_gh_under_xdg_cache_home() {
+        temp_env::with_vars(
+            [
+                (GH_CACHE_DIR, None),
+                (XDG_CACHE_HOME, Some("/xdg-cache-home")),
+            ],
+            || assert_eq!(cache_dir(), Some(PathBuf::from("/xdg-cache-home/gh"))),
+        );
+    }
+
+    #[test]
+    fn key_depends_on_method_url_token_and_body() {
+        let url = "https://api.github.com/graphql";
+        let expected = key(Method::POST, url, "ghp_xxxx", "{}");
+
+        assert_eq!(expected, key(Method::POST, url, "ghp_xxxx", "{}"));
+        assert_ne!(expected, key(Method::GET, url, "ghp_xxxx", "{}"));
+        assert_ne!(
+            expected,
+            key(
+                Method::POST,
+                "https://api.github.com/user",
+                "ghp_xxxx",
+                "{}"
+            )
+        );
+        assert_ne!(expected, key(Method::POST, url, "ghp_yyyy", "{}"));
+        assert_ne!(expected, key(Method::POST, url, "ghp_xxxx", "{ }"));
+    }
+
+    #[test]
+    fn is_cacheable_only_for_reads() {
+        let cacheable = |method, url: &str| Cache::is_cacheable(&method, &url.parse().unwrap());
+
+        assert!(cacheable(Method::GET, "https://api.github.com/user"));
+        assert!(cacheable(Method::POST, "https://my.ghes.com/api/graphql"));
+        assert!(!cacheable(
+            Method::POST,
+            "https://api.github.com/user/repos"
+        ));
+        assert!(!cacheable(Method::DELETE, "https://api.github.com/user"));
+    }
+
+    #[test]
+    fn get_returns_put_response_as_stale_once_ttl_passes() {
+        let dir = tempfile::tempdir().unwrap();
+        let response = Response {
+            url: "https://api.github.com/user".to_owned(),
+            status: StatusCode::OK,
+            headers: HeaderMap::from_iter([(
+                HeaderName::from_static("etag"),
+                HeaderValue::from_static("\"abc\""),
+            )]),
+            body: br#"{"login":"monalisa"}"#.to_vec(),
+        };
+
+        let cache = Cache {
+            dir: dir.path(),
+            ttl: Duration::from_secs(3600),
+        };
+        cache.put("key", &response);
+
+        assert_eq!(
+            cache.get("key"),
+            Some(Cached {
+                response: response.clone(),
+                fresh: true
+            })
+        );
+        assert_eq!(cache.get("other"), None);
+        assert_eq!(
+            Cache {
+                dir: dir.path(),
+                ttl: Duration::ZERO,
+            }
+            .get("key"),
+            Some(Cached {
+                response,
+                fresh: false
+            })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn put_keeps_responses_from_other_users() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache_dir = dir.path().join("gh");
+        let response = Response {
+            url: "https://api.github.com/user".to_owned(),
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+        };
+
+        Cache {
+            dir: &cache_dir,
+            ttl: Duration::from_secs(3600),
+        }
+        .put("key", &response);
+
+        let mode = |path: &Path| path.metadata().unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&cache_dir), 0o700);
+        assert_eq!(mode(&cache_dir.join("key")), 0o600);
+        assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn revalidate_sends_validators_of_stale_response() {
+        let mut headers = HeaderMap::new();
+        let stale = |headers: &[(HeaderName, &'static str)]| Response {
+            url: "https://api.github.com/user".to_owned(),
+            status: StatusCode::OK,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+                .collect(),
+            body: Vec::new(),
+        };
+
+        assert!(!Cache::revalidate(&mut headers, &stale(&[])));
+        assert!(Cache::revalidate(
+            &mut headers,
+            &stale(&[
+                (ETAG, "W/\"abc\""),
+                (LAST_MODIFIED, "Mon, 01 Jan 2024 00:00:00 GMT")
+            ])
+        ));
+        assert_eq!(headers[IF_NONE_MATCH], "W/\"abc\"");
+        assert_eq!(headers[IF_MODIFIED_SINCE], "Mon, 01 Jan 2024 00:00:00 GMT");
+    }
+}
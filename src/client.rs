@@ -0,0 +1,586 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use reqwest::{
+    blocking::{Client, RequestBuilder},
+    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT},
+    Method, StatusCode, Url,
+};
+
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    cache::{self, Cache, Cached},
+    host::Host,
+    HttpError, ParseHostError, RateLimit, RateLimitPolicy, SecretString, TokenError, TokenResolver,
+};
+
+const API_VERSION: &str = "2022-11-28";
+
+// Settings shared by the REST and GraphQL clients.
+//
+// RestClient::with_options(
+//     ClientOptions::new("github.com").extension_name("gh-my-extension"),
+// )
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    host: String,
+    extension_name: Option<String>,
+    auth_token: Option<SecretString>,
+    token_resolver: TokenResolver,
+    base_url: Option<String>,
+    graphql_url: Option<String>,
+    rate_limit_policy: Option<RateLimitPolicy>,
+    cache_ttl: Option<Duration>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl ClientOptions {
+    pub fn new(host: &str) -> Self {
+        Self {
+            host: host.to_owned(),
+            extension_name: None,
+            auth_token: None,
+            token_resolver: TokenResolver::new(),
+            base_url: None,
+            graphql_url: None,
+            rate_limit_policy: None,
+            cache_ttl: None,
+            cache_dir: None,
+        }
+    }
+
+    // Included in the User-Agent, so GitHub can tell extensions apart.
+    pub fn extension_name(mut self, extension_name: &str) -> Self {
+        self.extension_name = Some(extension_name.to_owned());
+        self
+    }
+
+    // Skips token resolution and uses this token instead.
+    pub fn auth_token(mut self, auth_token: impl Into<SecretString>) -> Self {
+        self.auth_token = Some(auth_token.into());
+        self
+    }
+
+    // How the token is found when no auth_token is given.
+    pub fn token_resolver(mut self, token_resolver: TokenResolver) -> Self {
+        self.token_resolver = token_resolver;
+        self
+    }
+
+    // Overrides the API URL derived from the host, e.g. to go through a proxy. GraphQL
+    // requests then go to <base_url>/graphql, unless graphql_url is set too.
+    pub fn base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Some(base_url.to_owned());
+        self
+    }
+
+    // Overrides the GraphQL endpoint derived from the host. Needed alongside a GHES style
+    // base_url, e.g. https://my.ghes.com/api/v3/, as GHES serves GraphQL from
+    // https://my.ghes.com/api/graphql.
+    pub fn graphql_url(mut self, graphql_url: &str) -> Self {
+        self.graphql_url = Some(graphql_url.to_owned());
+        self
+    }
+
+    // Waits and retries when rate limited, instead of failing.
+    pub fn rate_limit_policy(mut self, rate_limit_policy: RateLimitPolicy) -> Self {
+        self.rate_limit_policy = Some(rate_limit_policy);
+        self
+    }
+
+    // Caches responses to GET and GraphQL requests on disk for this long, like
+    // `gh api --cache`. Clients' with_cache_ttl sets it for single requests instead,
+    // which is safer for GraphQL as mutations would be cached too. Once the ttl passes,
+    // responses with an ETag or Last-Modified header are revalidated, so a zero ttl
+    // suits polling: unchanged responses come back as 304s, which are free.
+    pub fn cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = Some(cache_ttl);
+        self
+    }
+
+    // Where cached responses are kept, by default under gh's cache dir.
+    pub fn cache_dir(mut self, cache_dir: &Path) -> Self {
+        self.cache_dir = Some(cache_dir.to_owned());
+        self
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientError {
+    InvalidHost(ParseHostError),
+    Token(TokenError),
+    NoToken(Host),
+    InvalidToken, // can't be sent in an Authorization header
+    Request(String),
+    Http(Box<HttpError>), // boxed as it's much larger than the rest
+    Json(String),
+    GraphQL(Vec<GraphQLErrorItem>),
+}
+
+// One entry of the errors array in a GraphQL response, mirroring go-gh's
+// api.GraphQLErrorItem.
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct GraphQLErrorItem {
+    pub message: String,
+    #[serde(rename = "type")]
+    pub kind: Option<String>, // e.g. NOT_FOUND
+    #[serde(default)]
+    pub path: Vec<serde_json::Value>, // field names and list indices
+    #[serde(default)]
+    pub locations: Vec<GraphQLErrorLocation>,
+}
+
+#[derive(Debug, PartialEq, Eq, Deserialize)]
+pub struct GraphQLErrorLocation {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl std::fmt::Display for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHost(err) => err.fmt(f),
+            Self::Token(err) => err.fmt(f),
+            Self::NoToken(host) => write!(
+                f,
+                "authentication token not found for host {host}, try running `gh auth login`"
+            ),
+            Self::InvalidToken => write!(
+                f,
+                "authentication token contains characters that can't be sent in a header"
+            ),
+            Self::Request(reason) => write!(f, "request failed: {reason}"),
+            Self::Http(err) => err.fmt(f),
+            Self::Json(reason) => write!(f, "failed to parse response: {reason}"),
+            Self::GraphQL(errors) => {
+                let messages: Vec<_> = errors.iter().map(|err| err.message.as_str()).collect();
+                write!(f, "GraphQL: {}", messages.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<TokenError> for ClientError {
+    fn from(err: TokenError) -> Self {
+        Self::Token(err)
+    }
+}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::Request(err.to_string())
+    }
+}
+
+// A fully read response, which may have come from the cache.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct Response {
+    pub(crate) url: String,
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    pub(crate) fn json<T: DeserializeOwned>(&self) -> Result<T, ClientError> {
+        serde_json::from_slice(&self.body).map_err(|err| ClientError::Json(err.to_string()))
+    }
+}
+
+// What the clients share once the options have been resolved. Each client pairs it
+// with a blocking or async reqwest client of its own.
+#[derive(Debug, Clone)]
+pub(crate) struct Transport {
+    pub(crate) host: Host,
+    pub(crate) base_url: Option<String>,
+    pub(crate) graphql_url: Option<String>,
+    token: SecretString,
+    authorization: HeaderValue, // only sent to the host the token is for
+    headers: HeaderMap,         // sent with every request
+    rate_limit_policy: Option<RateLimitPolicy>,
+    rate_limit: Arc<Mutex<Option<RateLimit>>>, // from the latest response of any clone
+    cache_ttl: Option<Duration>,
+    cache_dir: Option<PathBuf>,
+}
+
+pub(crate) enum Prepared<'a> {
+    Cached(Response), // fresh in the cache, so there's no need to send the request
+    Send(Pending<'a>),
+}
+
+// What's needed to handle the response to a request that has to be sent.
+pub(crate) struct Pending<'a> {
+    cache: Option<(Cache<'a>, String)>,
+    stale: Option<Response>, // being revalidated
+}
+
+pub(crate) enum Received<R> {
+    Done(Result<Response, ClientError>),
+    Retry(R, Duration), // send the request again after waiting
+}
+
+impl Transport {
+    pub(crate) fn new(options: &ClientOptions) -> Result<Self, ClientError> {
+        let host = Self::host(options)?;
+        let token = match &options.auth_token {
+            Some(token) => token.clone(),
+            None => options
+                .token_resolver
+                .token_for_host(host.as_str())?
+                .map(|token| token.value)
+                .ok_or_else(|| ClientError::NoToken(host.clone()))?,
+        };
+
+        Self::with_token(options, host, token)
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) async fn new_async(options: &ClientOptions) -> Result<Self, ClientError> {
+        let host = Self::host(options)?;
+        let token = match &options.auth_token {
+            Some(token) => token.clone(),
+            None => options
+                .token_resolver
+                .token_for_host_async(host.as_str())
+                .await?
+                .map(|token| token.value)
+                .ok_or_else(|| ClientError::NoToken(host.clone()))?,
+        };
+
+        Self::with_token(options, host, token)
+    }
+
+    fn host(options: &ClientOptions) -> Result<Host, ClientError> {
+        options
+            .host
+            .parse::<Host>()
+            .map_err(ClientError::InvalidHost)
+    }
+
+    fn with_token(
+        options: &ClientOptions,
+        host: Host,
+        token: SecretString,
+    ) -> Result<Self, ClientError> {
+        let user_agent = match &options.extension_name {
+            Some(name) => format!("{name} ghet-rektstension/{}", env!("CARGO_PKG_VERSION")),
+            None => format!("ghet-rektstension/{}", env!("CARGO_PKG_VERSION")),
+        };
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/vnd.github+json"),
+        );
+        headers.insert(
+            "X-GitHub-Api-Version",
+            HeaderValue::from_static(API_VERSION),
+        );
+        headers.insert(
+            USER_AGENT,
+            HeaderValue::from_str(&user_agent)
+                .map_err(|err| ClientError::Request(err.to_string()))?,
+        );
+        let authorization = token
+            .authorization_header()
+            .map_err(|_| ClientError::InvalidToken)?;
+
+        Ok(Self {
+            host,
+            base_url: options.base_url.clone(),
+            graphql_url: options.graphql_url.clone(),
+            token,
+            authorization,
+            headers,
+            rate_limit_policy: options.rate_limit_policy,
+            rate_limit: Arc::default(),
+            cache_ttl: options.cache_ttl,
+            cache_dir: options
+                .cache_dir
+                .clone()
+                .or_else(|| cache::cache_dir().map(|dir| dir.join("api-cache"))),
+        })
+    }
+
+    pub(crate) fn client(&self) -> Result<Client, ClientError> {
+        Ok(Client::builder()
+            .default_headers(self.headers.clone())
+            .build()?)
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn async_client(&self) -> Result<reqwest::Client, ClientError> {
+        Ok(reqwest::Client::builder()
+            .default_headers(self.headers.clone())
+            .build()?)
+    }
+
+    pub(crate) fn with_cache_ttl(&self, cache_ttl: Duration) -> Self {
+        Self {
+            cache_ttl: Some(cache_ttl),
+            ..self.clone()
+        }
+    }
+
+    // Relative paths are resolved against base_url, while absolute URLs are used as is.
+    pub(crate) fn url(base_url: &str, path: &str) -> String {
+        if path.starts_with("https://") || path.starts_with("http://") {
+            return path.to_owned();
+        }
+
+        format!(
+            "{}/{}",
+            base_url.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    // Like go-gh, the token is only sent to its own host, or to the hosts of overridden
+    // URLs, so that absolute URLs, e.g. from Link headers, can't hand it to anyone else.
+    fn authorize(&self, url: &Url, headers: &mut HeaderMap) {
+        let host_of = |url: &str| {
+            Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str()?.parse::<Host>().ok())
+        };
+        let Some(host) = host_of(url.as_str()) else {
+            return;
+        };
+
+        let trusted = host == self.host
+            || [&self.base_url, &self.graphql_url]
+                .into_iter()
+                .flatten()
+                .any(|trusted| host_of(trusted).as_ref() == Some(&host));
+
+        if trusted {
+            headers.insert(AUTHORIZATION, self.authorization.clone());
+        }
+    }
+
+    // Answers the request from the cache when it's fresh there. Stale cached responses
+    // are revalidated rather than fetched again, by adding conditions to the headers.
+    pub(crate) fn prepare(
+        &self,
+        method: &Method,
+        url: &Url,
+        headers: &mut HeaderMap,
+        body: Option<&[u8]>,
+    ) -> Prepared<'_> {
+        let cache = self
+            .cache_dir
+            .as_deref()
+            .zip(self.cache_ttl)
+            .map(|(dir, ttl)| Cache { dir, ttl })
+            .filter(|_| Cache::is_cacheable(method, url))
+            .map(|cache| (cache, Cache::key(method, url, &self.token, body)));
+
+        let stale = match cache.as_ref().and_then(|(cache, key)| cache.get(key)) {
+            Some(Cached {
+                response,
+                fresh: true,
+            }) => return Prepared::Cached(response),
+            Some(Cached { response, .. }) if Cache::revalidate(headers, &response) => {
+                Some(response)
+            }
+            _ => None,
+        };
+
+        Prepared::Send(Pending { cache, stale })
+    }
+
+    // Fails for any unsuccessful status, unless the rate limit policy says to retry.
+    // retry is a copy of the request to send again, if it could be copied at all.
+    pub(crate) fn receive<R>(
+        &self,
+        pending: &Pending,
+        response: Response,
+        attempt: u32,
+        retry: Option<R>,
+    ) -> Received<R> {
+        let rate_limit = RateLimit::from_headers(&response.headers);
+        if let (Some(rate_limit), Ok(mut latest)) = (&rate_limit, self.rate_limit.lock()) {
+            *latest = Some(rate_limit.clone());
+        }
+
+        if let (StatusCode::NOT_MODIFIED, Some(stale), Some((cache, key))) =
+            (response.status, &pending.stale, &pending.cache)
+        {
+            // Storing it again restarts its ttl.
+            cache.put(key, stale);
+            return Received::Done(Ok(stale.clone()));
+        }
+
+        if response.status.is_success() {
+            if let Some((cache, key)) = &pending.cache {
+                cache.put(key, &response);
+            }
+            return Received::Done(Ok(response));
+        }
+
+        let body = String::from_utf8_lossy(&response.body);
+        let wait = self
+            .rate_limit_policy
+            .and_then(|policy| policy.wait(response.status, rate_limit.as_ref(), &body, attempt));
+
+        match (retry, wait) {
+            (Some(retry), Some(wait)) => Received::Retry(retry, wait),
+            _ => {
+                let err = HttpError::new(response.status, &response.url, response.headers, &body);
+                Received::Done(Err(ClientError::Http(Box::new(err))))
+            }
+        }
+    }
+
+    pub(crate) fn send(
+        &self,
+        client: &Client,
+        request: RequestBuilder,
+    ) -> Result<Response, ClientError> {
+        let mut request = request.build()?;
+        let (method, url) = (request.method().clone(), request.url().clone());
+        self.authorize(&url, request.headers_mut());
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(<[u8]>::to_vec);
+
+        let pending = match self.prepare(&method, &url, request.headers_mut(), body.as_deref()) {
+            Prepared::Cached(response) => return Ok(response),
+            Prepared::Send(pending) => pending,
+        };
+
+        let mut attempt = 0;
+        loop {
+            // Requests with streaming bodies can't be copied, and so can't be retried.
+            let retry = self.rate_limit_policy.and_then(|_| request.try_clone());
+
+            let response = client.execute(request)?;
+            let response = Response {
+                url: response.url().to_string(),
+                status: response.status(),
+                headers: response.headers().clone(),
+                body: response.bytes()?.to_vec(),
+            };
+
+            match self.receive(&pending, response, attempt, retry) {
+                Received::Done(result) => return result,
+                Received::Retry(retry, wait) => {
+                    std::thread::sleep(wait);
+                    request = retry;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) async fn send_async(
+        &self,
+        client: &reqwest::Client,
+        request: reqwest::RequestBuilder,
+    ) -> Result<Response, ClientError> {
+        let mut request = request.build()?;
+        let (method, url) = (request.method().clone(), request.url().clone());
+        self.authorize(&url, request.headers_mut());
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(<[u8]>::to_vec);
+
+        let pending = match self.prepare(&method, &url, request.headers_mut(), body.as_deref()) {
+            Prepared::Cached(response) => return Ok(response),
+            Prepared::Send(pending) => pending,
+        };
+
+        let mut attempt = 0;
+        loop {
+            let retry = self.rate_limit_policy.and_then(|_| request.try_clone());
+
+            let response = client.execute(request).await?;
+            let response = Response {
+                url: response.url().to_string(),
+                status: response.status(),
+                headers: response.headers().clone(),
+                body: response.bytes().await?.to_vec(),
+            };
+
+            match self.receive(&pending, response, attempt, retry) {
+                Received::Done(result) => return result,
+                Received::Retry(retry, wait) => {
+                    tokio::time::sleep(wait).await;
+                    request = retry;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    // Shared by the clients' clones and with_cache_ttl copies, so with concurrent requests
+    // it may describe another request's response. None before any response carried one.
+    // A failed request's own rate limit is on its HttpError.
+    pub(crate) fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+            .lock()
+            .map(|rate_limit| rate_limit.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_joins_paths_onto_base_url() {
+        assert_eq!(
+            Transport::url("https://api.github.com/", "/repos/cli/cli"),
+            "https://api.github.com/repos/cli/cli"
+        );
+        assert_eq!(
+            Transport::url("https://my.ghes.com/api/v3/", "user"),
+            "https://my.ghes.com/api/v3/user"
+        );
+        assert_eq!(
+            Transport::url("https://api.github.com/", "https://uploads.github.com/x"),
+            "https://uploads.github.com/x"
+        );
+    }
+
+    #[test]
+    fn transport_fails_for_a_token_that_cannot_be_sent() {
+        let options = ClientOptions::new("github.com").auth_token("ghp_xxxx\n");
+
+        assert_eq!(
+            Transport::new(&options).map(|_| ()),
+            Err(ClientError::InvalidToken)
+        );
+    }
+
+    #[test]
+    fn transport_fails_without_a_token() {
+        let dir = tempfile::tempdir().unwrap();
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_ENTERPRISE_TOKEN", None),
+                ("GITHUB_ENTERPRISE_TOKEN", None),
+            ],
+            || {
+                let options = ClientOptions::new("my.ghes.com")
+                    .token_resolver(TokenResolver::new().sources([]));
+
+                assert_eq!(
+                    Transport::new(&options).map(|_| ()),
+                    Err(ClientError::NoToken("my.ghes.com".parse().unwrap()))
+                );
+            },
+        );
+    }
+}
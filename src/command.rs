@@ -0,0 +1,94 @@
+use std::{ffi::OsStr, process::Command};
+
+#[cfg(feature = "async")]
+use std::{future::Future, pin::Pin};
+use zeroize::Zeroize;
+
+// What a gh or git command printed. As gh auth token prints the token itself, stdout is
+// wiped on drop and left out of Debug.
+#[derive(PartialEq, Eq, Clone, Default)]
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl std::fmt::Debug for CommandOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandOutput")
+            .field("success", &self.success)
+            .field("stdout", &format_args!("[{} bytes]", self.stdout.len()))
+            .field("stderr", &String::from_utf8_lossy(&self.stderr))
+            .finish()
+    }
+}
+
+impl Drop for CommandOutput {
+    fn drop(&mut self) {
+        self.stdout.zeroize();
+    }
+}
+
+// Runs `gh auth token` for keyring lookups, and `git remote -v` and `git config` for
+// current_repository. Swapping this out lets tests script the responses, and lets
+// callers run the commands some other way entirely.
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, program: &OsStr, args: &[&str]) -> std::io::Result<CommandOutput>;
+
+    // Used by async token resolution. Defaults to calling run, which is fine for
+    // runners that don't actually block, like test fakes.
+    #[cfg(feature = "async")]
+    fn run_async<'a>(
+        &'a self,
+        program: &'a OsStr,
+        args: &'a [&'a str],
+    ) -> Pin<Box<dyn Future<Output = std::io::Result<CommandOutput>> + Send + 'a>> {
+        Box::pin(std::future::ready(self.run(program, args)))
+    }
+}
+
+impl<F> CommandRunner for F
+where
+    F: Fn(&OsStr, &[&str]) -> std::io::Result<CommandOutput> + Send + Sync,
+{
+    fn run(&self, program: &OsStr, args: &[&str]) -> std::io::Result<CommandOutput> {
+        self(program, args)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessRunner;
+
+impl CommandRunner for ProcessRunner {
+    fn run(&self, program: &OsStr, args: &[&str]) -> std::io::Result<CommandOutput> {
+        Command::new(program)
+            .args(args)
+            .output()
+            .map(CommandOutput::from)
+    }
+
+    #[cfg(feature = "async")]
+    fn run_async<'a>(
+        &'a self,
+        program: &'a OsStr,
+        args: &'a [&'a str],
+    ) -> Pin<Box<dyn Future<Output = std::io::Result<CommandOutput>> + Send + 'a>> {
+        Box::pin(async move {
+            tokio::process::Command::new(program)
+                .args(args)
+                .output()
+                .await
+                .map(CommandOutput::from)
+        })
+    }
+}
+
+impl From<std::process::Output> for CommandOutput {
+    fn from(output: std::process::Output) -> Self {
+        Self {
+            success: output.status.success(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+    }
+}
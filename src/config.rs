@@ -0,0 +1,244 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::Deserialize;
+use zeroize::Zeroizing;
+
+use crate::{host::Host, SecretString};
+
+const GH_CONFIG_DIR: &str = "GH_CONFIG_DIR";
+const XDG_CONFIG_HOME: &str = "XDG_CONFIG_HOME";
+const APP_DATA: &str = "AppData";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenFromConfigError {
+    FailToRead(std::io::ErrorKind),
+    FailToParse(String),
+}
+
+impl std::fmt::Display for TokenFromConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FailToRead(kind) => write!(f, "failed to read gh hosts config: {kind}"),
+            Self::FailToParse(reason) => write!(f, "failed to parse gh hosts config: {reason}"),
+        }
+    }
+}
+
+// The subset of an entry in gh's hosts.yml that we care about, e.g.
+//
+// github.com:
+//     users:
+//         williammartin:
+//             oauth_token: gho_xxxx
+//         monalisa:
+//     oauth_token: gho_xxxx
+//     user: williammartin
+//     git_protocol: https
+//
+// The top level oauth_token and user belong to the active account. Tokens only appear
+// in the file when gh was told to use insecure storage.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct HostConfig {
+    pub(crate) oauth_token: Option<SecretString>,
+    pub(crate) user: Option<String>,
+    pub(crate) users: Option<BTreeMap<String, Option<UserConfig>>>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct UserConfig {
+    pub(crate) oauth_token: Option<SecretString>,
+}
+
+impl HostConfig {
+    pub(crate) fn user_token(&mut self, user: &str) -> Option<SecretString> {
+        self.users
+            .as_mut()
+            .and_then(|users| users.remove(user))
+            .flatten()
+            .and_then(|user_config| user_config.oauth_token)
+    }
+
+    // Configs written before gh supported multiple accounts only have a top level user.
+    pub(crate) fn usernames(&self) -> Vec<String> {
+        match &self.users {
+            Some(users) if !users.is_empty() => users.keys().cloned().collect(),
+            _ => self.user.iter().cloned().collect(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Hosts {
+    pub(crate) path: String,
+    pub(crate) hosts: BTreeMap<Host, HostConfig>,
+}
+
+// Mirrors gh's config.ConfigDir, minus the legacy migration handling.
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    gh_dir(GH_CONFIG_DIR, XDG_CONFIG_HOME, APP_DATA, ".config")
+}
+
+// gh's directories all follow the same rules: its own variable wins, then the XDG
+// variable, then the Windows one, and finally a directory under home, e.g. ~/.config/gh.
+pub(crate) fn gh_dir(
+    gh_var: &str,
+    xdg_var: &str,
+    windows_var: &str,
+    home: &str,
+) -> Option<PathBuf> {
+    non_empty_var(gh_var)
+        .map(PathBuf::from)
+        .or_else(|| non_empty_var(xdg_var).map(|dir| PathBuf::from(dir).join("gh")))
+        .or_else(|| {
+            non_empty_var(windows_var)
+                .filter(|_| cfg!(windows))
+                .map(|dir| PathBuf::from(dir).join("GitHub CLI"))
+        })
+        .or_else(|| std::env::home_dir().map(|dir| dir.join(home).join("gh")))
+}
+
+pub(crate) fn load_hosts() -> Result<Option<Hosts>, TokenFromConfigError> {
+    let Some(path) = config_dir().map(|dir| dir.join("hosts.yml")) else {
+        return Ok(None);
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => Zeroizing::new(contents),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(TokenFromConfigError::FailToRead(err.kind())),
+    };
+
+    parse_hosts(&contents).map(|hosts| {
+        Some(Hosts {
+            path: path.to_string_lossy().into_owned(),
+            hosts,
+        })
+    })
+}
+
+fn parse_hosts(contents: &str) -> Result<BTreeMap<Host, HostConfig>, TokenFromConfigError> {
+    // An empty file deserializes to a YAML null rather than an empty mapping.
+    serde_yaml::from_str::<Option<BTreeMap<String, Option<HostConfig>>>>(contents)
+        .map_err(|err| TokenFromConfigError::FailToParse(err.to_string()))
+        .map(|hosts| {
+            hosts
+                .unwrap_or_default()
+                .into_iter()
+                // gh only ever writes valid hostnames here, so anything else is ignored.
+                .filter_map(|(host, config)| {
+                    host.parse::<Host>()
+                        .ok()
+                        .map(|host| (host, config.unwrap_or_default()))
+                })
+                .collect()
+        })
+}
+
+// gh treats variables that are set but empty as unset.
+pub(crate) fn non_empty_var(key: &str) -> Option<String> {
+    std::env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_dir_prefers_gh_config_dir() {
+        temp_env::with_vars(
+            [
+                (GH_CONFIG_DIR, Some("/gh-config-dir")),
+                (XDG_CONFIG_HOME, Some("/xdg-config-home")),
+            ],
+            || assert_eq!(config_dir(), Some(PathBuf::from("/gh-config-dir"))),
+        );
+    }
+
+    #[test]
+    fn config_dir_uses_gh_under_xdg_config_home() {
+        temp_env::with_vars(
+            [
+                (GH_CONFIG_DIR, None),
+                (XDG_CONFIG_HOME, Some("/xdg-config-home")),
+            ],
+            || assert_eq!(config_dir(), Some(PathBuf::from("/xdg-config-home/gh"))),
+        );
+    }
+
+    #[test]
+    fn load_hosts_returns_none_when_file_is_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        temp_env::with_var(GH_CONFIG_DIR, Some(dir.path()), || {
+            assert_eq!(load_hosts(), Ok(None))
+        });
+    }
+
+    #[test]
+    fn load_hosts_reads_hosts_yml_in_config_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(
+            &path,
+            "github.com:\n    oauth_token: gho_xxxx\n    user: monalisa\n    git_protocol: https\n",
+        )
+        .unwrap();
+
+        temp_env::with_var(GH_CONFIG_DIR, Some(dir.path()), || {
+            assert_eq!(
+                load_hosts(),
+                Ok(Some(Hosts {
+                    path: path.to_string_lossy().into_owned(),
+                    hosts: BTreeMap::from([(
+                        "github.com".parse().unwrap(),
+                        HostConfig {
+                            oauth_token: Some("gho_xxxx".into()),
+                            user: Some("monalisa".to_owned()),
+                            users: None,
+                        }
+                    )]),
+                }))
+            )
+        });
+    }
+
+    #[test]
+    fn parse_hosts_accepts_empty_file_and_empty_host() {
+        assert_eq!(parse_hosts(""), Ok(BTreeMap::new()));
+        assert_eq!(
+            parse_hosts("my.ghes.com:\n"),
+            Ok(BTreeMap::from([(
+                "my.ghes.com".parse().unwrap(),
+                HostConfig::default()
+            )]))
+        );
+    }
+
+    #[test]
+    fn parse_hosts_fails_on_malformed_yaml() {
+        assert!(matches!(
+            parse_hosts("github.com: [oops"),
+            Err(TokenFromConfigError::FailToParse(_))
+        ));
+    }
+
+    #[test]
+    fn host_config_reads_tokens_and_usernames_per_user() {
+        let mut hosts = parse_hosts(
+            "github.com:\n    users:\n        monalisa:\n            oauth_token: a\n        hubot:\n    user: monalisa\n    oauth_token: a\n",
+        )
+        .unwrap();
+        let host_config = hosts.get_mut(&Host::github()).unwrap();
+
+        assert_eq!(host_config.usernames(), vec!["hubot", "monalisa"]);
+        assert_eq!(host_config.user_token("monalisa"), Some("a".into()));
+        assert_eq!(host_config.user_token("hubot"), None);
+        assert_eq!(host_config.user_token("unknown"), None);
+    }
+
+    #[test]
+    fn host_config_usernames_falls_back_to_active_user() {
+        let hosts = parse_hosts("github.com:\n    user: monalisa\n").unwrap();
+
+        assert_eq!(hosts[&Host::github()].usernames(), vec!["monalisa"]);
+    }
+}
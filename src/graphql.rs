@@ -0,0 +1,635 @@
+use std::{collections::VecDeque, marker::PhantomData, time::Duration};
+
+use reqwest::{blocking::Client, Method};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    client::{ClientError, ClientOptions, GraphQLErrorItem, Response, Transport},
+    RateLimit,
+};
+
+// A client for the GraphQL API of a single host, authenticated with the host's token.
+//
+// let client = GraphQLClient::new("github.com")?;
+// let data: Data = client.query(QUERY, &json!({ "owner": "cli", "name": "cli" }))?;
+#[derive(Debug, Clone)]
+pub struct GraphQLClient {
+    transport: Transport,
+    client: Client,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct Request<'a, V: Serialize + ?Sized> {
+    query: &'a str,
+    variables: &'a V,
+}
+
+// data is kept as raw JSON until errors are checked, since partial data that comes
+// with errors usually won't fit the caller's type.
+#[derive(Deserialize)]
+struct ResponseBody {
+    data: Option<serde_json::Value>,
+    #[serde(default)]
+    errors: Vec<GraphQLErrorItem>,
+}
+
+impl GraphQLClient {
+    pub fn new(host: &str) -> Result<Self, ClientError> {
+        Self::with_options(ClientOptions::new(host))
+    }
+
+    pub fn with_options(options: ClientOptions) -> Result<Self, ClientError> {
+        let transport = Transport::new(&options)?;
+
+        Ok(Self {
+            client: transport.client()?,
+            url: graphql_url(&transport),
+            transport,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // A copy of this client that caches responses for the given time, e.g. for a
+    // single query. As GraphQL requests are all POSTs, don't use it for mutations.
+    pub fn with_cache_ttl(&self, cache_ttl: Duration) -> Self {
+        Self {
+            transport: self.transport.with_cache_ttl(cache_ttl),
+            ..self.clone()
+        }
+    }
+
+    // The rate limit as of the latest response to this client or its clones.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.transport.rate_limit()
+    }
+
+    // Runs a query or mutation, deserializing its data into T. Like gh, any errors in
+    // the response fail the whole request, even if some data came back too.
+    pub fn query<V: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: &V,
+    ) -> Result<T, ClientError> {
+        self.data(query, variables).and_then(from_data)
+    }
+
+    // Walks every page of a connection, yielding its nodes one at a time, like
+    // `gh api graphql --paginate`. The query must take an `$endCursor: String` variable,
+    // pass it as the connection's `after` argument and select `pageInfo { hasNextPage
+    // endCursor }` along with `nodes` or `edges { node }`. The connection is found at a
+    // dotted path under data, e.g. "repository.issues".
+    //
+    // for issue in client.paginate::<Issue>(QUERY, json!({ "owner": "cli" }), "repository.issues") {
+    //     println!("{}", issue?.title);
+    // }
+    pub fn paginate<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: impl Serialize,
+        connection: &str,
+    ) -> GraphQLPaginate<'_, T> {
+        GraphQLPaginate {
+            client: self,
+            pages: Pages::new(query, variables, connection),
+            node: PhantomData,
+        }
+    }
+
+    fn data<V: Serialize + ?Sized>(
+        &self,
+        query: &str,
+        variables: &V,
+    ) -> Result<serde_json::Value, ClientError> {
+        let request = self
+            .client
+            .request(Method::POST, &self.url)
+            .json(&Request { query, variables });
+
+        self.transport.send(&self.client, request).and_then(data_of)
+    }
+}
+
+// The async counterpart of GraphQLClient, for use with tokio.
+//
+// let client = AsyncGraphQLClient::new("github.com").await?;
+// let data: Data = client.query(QUERY, &json!({ "owner": "cli", "name": "cli" })).await?;
+#[cfg(feature = "async")]
+#[derive(Debug, Clone)]
+pub struct AsyncGraphQLClient {
+    transport: Transport,
+    client: reqwest::Client,
+    url: String,
+}
+
+#[cfg(feature = "async")]
+impl AsyncGraphQLClient {
+    pub async fn new(host: &str) -> Result<Self, ClientError> {
+        Self::with_options(ClientOptions::new(host)).await
+    }
+
+    pub async fn with_options(options: ClientOptions) -> Result<Self, ClientError> {
+        let transport = Transport::new_async(&options).await?;
+
+        Ok(Self {
+            client: transport.async_client()?,
+            url: graphql_url(&transport),
+            transport,
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn with_cache_ttl(&self, cache_ttl: Duration) -> Self {
+        Self {
+            transport: self.transport.with_cache_ttl(cache_ttl),
+            ..self.clone()
+        }
+    }
+
+    // The rate limit as of the latest response to this client or its clones.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.transport.rate_limit()
+    }
+
+    pub async fn query<V: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: &V,
+    ) -> Result<T, ClientError> {
+        self.data(query, variables).await.and_then(from_data)
+    }
+
+    // while let Some(issue) = issues.next().await { .. }
+    pub fn paginate<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: impl Serialize,
+        connection: &str,
+    ) -> AsyncGraphQLPaginate<'_, T> {
+        AsyncGraphQLPaginate {
+            client: self,
+            pages: Pages::new(query, variables, connection),
+            node: PhantomData,
+        }
+    }
+
+    async fn data<V: Serialize + ?Sized>(
+        &self,
+        query: &str,
+        variables: &V,
+    ) -> Result<serde_json::Value, ClientError> {
+        let request = self
+            .client
+            .request(Method::POST, &self.url)
+            .json(&Request { query, variables });
+
+        self.transport
+            .send_async(&self.client, request)
+            .await
+            .and_then(data_of)
+    }
+}
+
+fn graphql_url(transport: &Transport) -> String {
+    match (&transport.graphql_url, &transport.base_url) {
+        (Some(graphql_url), _) => graphql_url.clone(),
+        (None, Some(base_url)) => Transport::url(base_url, "graphql"),
+        (None, None) => transport.host.graphql_url(),
+    }
+}
+
+fn data_of(response: Response) -> Result<serde_json::Value, ClientError> {
+    let body = response.json::<ResponseBody>()?;
+
+    if !body.errors.is_empty() {
+        return Err(ClientError::GraphQL(body.errors));
+    }
+
+    body.data
+        .ok_or_else(|| ClientError::Json("response has no data".to_owned()))
+}
+
+fn from_data<T: DeserializeOwned>(data: serde_json::Value) -> Result<T, ClientError> {
+    serde_json::from_value(data).map_err(|err| ClientError::Json(err.to_string()))
+}
+
+// Iterator returned by GraphQLClient::paginate. A failed request is yielded as an error
+// and ends the iteration.
+#[derive(Debug)]
+pub struct GraphQLPaginate<'a, T> {
+    client: &'a GraphQLClient,
+    pages: Pages,
+    node: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Iterator for GraphQLPaginate<'_, T> {
+    type Item = Result<T, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(node) = self.pages.pop() {
+                return Some(node);
+            }
+
+            let data = self
+                .pages
+                .next_variables()?
+                .and_then(|variables| self.client.data(&self.pages.query, &variables));
+            if let Err(err) = data.and_then(|data| self.pages.add(data)) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+// Returned by AsyncGraphQLClient::paginate, like GraphQLPaginate but with an async next.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncGraphQLPaginate<'a, T> {
+    client: &'a AsyncGraphQLClient,
+    pages: Pages,
+    node: PhantomData<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T: DeserializeOwned> AsyncGraphQLPaginate<'_, T> {
+    pub async fn next(&mut self) -> Option<Result<T, ClientError>> {
+        loop {
+            if let Some(node) = self.pages.pop() {
+                return Some(node);
+            }
+
+            let data = match self.pages.next_variables()? {
+                Ok(variables) => self.client.data(&self.pages.query, &variables).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = data.and_then(|data| self.pages.add(data)) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Edge {
+    node: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Connection {
+    nodes: Option<Vec<serde_json::Value>>,
+    edges: Option<Vec<Edge>>,
+    page_info: PageInfo,
+}
+
+// How far a pagination has got, whether blocking or async.
+#[derive(Debug)]
+struct Pages {
+    query: String,
+    variables: Result<serde_json::Value, String>,
+    pointer: String, // JSON pointer to the connection, e.g. "/repository/issues"
+    end_cursor: Option<String>,
+    done: bool,
+    nodes: VecDeque<serde_json::Value>,
+}
+
+impl Pages {
+    fn new(query: &str, variables: impl Serialize, connection: &str) -> Self {
+        Self {
+            query: query.to_owned(),
+            variables: serde_json::to_value(variables).map_err(|err| err.to_string()),
+            pointer: connection
+                .split('.')
+                .map(|field| format!("/{field}"))
+                .collect(),
+            end_cursor: None,
+            done: false,
+            nodes: VecDeque::new(),
+        }
+    }
+
+    // The variables for fetching the next page, or None once the last page has been
+    // fetched, or a request failed.
+    fn next_variables(
+        &mut self,
+    ) -> Option<Result<serde_json::Map<String, serde_json::Value>, ClientError>> {
+        if self.done {
+            return None;
+        }
+
+        // Stays done unless the page says there's another one.
+        self.done = true;
+
+        let mut variables = match &self.variables {
+            Ok(serde_json::Value::Object(variables)) => variables.clone(),
+            Ok(serde_json::Value::Null) => serde_json::Map::new(),
+            Ok(_) => {
+                return Some(Err(ClientError::Json(
+                    "variables must be an object".to_owned(),
+                )))
+            }
+            Err(reason) => return Some(Err(ClientError::Json(reason.clone()))),
+        };
+        // Like gh, the caller's own endCursor is kept for the first page, so that a
+        // pagination can be resumed.
+        if let Some(end_cursor) = self.end_cursor.take() {
+            variables.insert("endCursor".to_owned(), end_cursor.into());
+        }
+
+        Some(Ok(variables))
+    }
+
+    fn add(&mut self, mut data: serde_json::Value) -> Result<(), ClientError> {
+        let connection = data
+            .pointer_mut(&self.pointer)
+            .map(serde_json::Value::take)
+            .ok_or_else(|| ClientError::Json(format!("no connection at {}", self.pointer)))?;
+        let connection = serde_json::from_value::<Connection>(connection)
+            .map_err(|err| ClientError::Json(err.to_string()))?;
+
+        let nodes = match (connection.nodes, connection.edges) {
+            (Some(nodes), _) => nodes,
+            (None, Some(edges)) => edges.into_iter().map(|edge| edge.node).collect(),
+            (None, None) => {
+                return Err(ClientError::Json(
+                    "connection has neither nodes nor edges".to_owned(),
+                ))
+            }
+        };
+        self.nodes.extend(nodes);
+
+        if connection.page_info.has_next_page {
+            self.end_cursor = connection.page_info.end_cursor;
+            self.done = self.end_cursor.is_none();
+        }
+
+        Ok(())
+    }
+
+    fn pop<T: DeserializeOwned>(&mut self) -> Option<Result<T, ClientError>> {
+        self.nodes.pop_front().map(from_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::client::GraphQLErrorLocation;
+
+    const QUERY: &str = "query($owner: String!, $name: String!) { repository(owner: $owner, name: $name) { nameWithOwner } }";
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Repository {
+        name_with_owner: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Data {
+        repository: Repository,
+    }
+
+    fn client(server: &mockito::Server) -> GraphQLClient {
+        GraphQLClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .base_url(&server.url()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn graphql_client_uses_graphql_url_for_host() {
+        let graphql_url = |host: &str| {
+            GraphQLClient::with_options(ClientOptions::new(host).auth_token("ghp_xxxx"))
+                .unwrap()
+                .url()
+                .to_owned()
+        };
+
+        assert_eq!(graphql_url("github.com"), "https://api.github.com/graphql");
+        assert_eq!(
+            graphql_url("my.ghes.com"),
+            "https://my.ghes.com/api/graphql"
+        );
+    }
+
+    #[test]
+    fn graphql_client_prefers_graphql_url_over_base_url() {
+        let graphql_url = |options: ClientOptions| {
+            GraphQLClient::with_options(options.auth_token("ghp_xxxx"))
+                .unwrap()
+                .url()
+                .to_owned()
+        };
+        let options = ClientOptions::new("my.ghes.com").base_url("https://proxy.example.com/");
+
+        assert_eq!(
+            graphql_url(options.clone()),
+            "https://proxy.example.com/graphql"
+        );
+        assert_eq!(
+            graphql_url(options.graphql_url("https://proxy.example.com/api/graphql")),
+            "https://proxy.example.com/api/graphql"
+        );
+    }
+
+    #[test]
+    fn query_sends_variables_and_deserializes_data() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/graphql")
+            .match_header("authorization", "token ghp_xxxx")
+            .match_body(mockito::Matcher::Json(json!({
+                "query": QUERY,
+                "variables": { "owner": "cli", "name": "cli" },
+            })))
+            .with_body(r#"{"data":{"repository":{"nameWithOwner":"cli/cli"}}}"#)
+            .create();
+
+        assert_eq!(
+            client(&server).query::<_, Data>(QUERY, &json!({ "owner": "cli", "name": "cli" })),
+            Ok(Data {
+                repository: Repository {
+                    name_with_owner: "cli/cli".to_owned()
+                }
+            })
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn query_surfaces_errors_array() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/graphql")
+            .with_body(
+                r#"{"data":{"repository":null},"errors":[{"type":"NOT_FOUND","path":["repository"],"locations":[{"line":1,"column":51}],"message":"Could not resolve to a Repository with the name 'cli/missing'."}]}"#,
+            )
+            .create();
+
+        assert_eq!(
+            client(&server).query::<_, Data>(QUERY, &json!({ "owner": "cli", "name": "missing" })),
+            Err(ClientError::GraphQL(vec![GraphQLErrorItem {
+                message: "Could not resolve to a Repository with the name 'cli/missing'."
+                    .to_owned(),
+                kind: Some("NOT_FOUND".to_owned()),
+                path: vec![json!("repository")],
+                locations: vec![GraphQLErrorLocation {
+                    line: 1,
+                    column: 51
+                }],
+            }]))
+        );
+    }
+
+    const ISSUES_QUERY: &str = "query($owner: String!, $endCursor: String) { repository(owner: $owner, name: \"cli\") { issues(first: 2, after: $endCursor) { nodes { number } pageInfo { hasNextPage endCursor } } } }";
+
+    #[test]
+    fn paginate_follows_end_cursor() {
+        let mut server = mockito::Server::new();
+        let first = server
+            .mock("POST", "/graphql")
+            .match_body(mockito::Matcher::Json(json!({
+                "query": ISSUES_QUERY,
+                "variables": { "owner": "cli" },
+            })))
+            .with_body(r#"{"data":{"repository":{"issues":{"nodes":[{"number":1},{"number":2}],"pageInfo":{"hasNextPage":true,"endCursor":"Y3Vyc29yOjI="}}}}}"#)
+            .create();
+        let second = server
+            .mock("POST", "/graphql")
+            .match_body(mockito::Matcher::Json(json!({
+                "query": ISSUES_QUERY,
+                "variables": { "owner": "cli", "endCursor": "Y3Vyc29yOjI=" },
+            })))
+            .with_body(r#"{"data":{"repository":{"issues":{"nodes":[{"number":3}],"pageInfo":{"hasNextPage":false,"endCursor":"Y3Vyc29yOjM="}}}}}"#)
+            .create();
+
+        let client = client(&server);
+        let numbers: Result<Vec<_>, _> = client
+            .paginate::<serde_json::Value>(
+                ISSUES_QUERY,
+                json!({ "owner": "cli" }),
+                "repository.issues",
+            )
+            .map(|issue| issue.map(|issue| issue["number"].clone()))
+            .collect();
+
+        assert_eq!(numbers, Ok(vec![json!(1), json!(2), json!(3)]));
+        first.assert();
+        second.assert();
+    }
+
+    #[test]
+    fn paginate_resumes_from_callers_end_cursor() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/graphql")
+            .match_body(mockito::Matcher::Json(json!({
+                "query": ISSUES_QUERY,
+                "variables": { "owner": "cli", "endCursor": "Y3Vyc29yOjI=" },
+            })))
+            .with_body(r#"{"data":{"repository":{"issues":{"nodes":[{"number":3}],"pageInfo":{"hasNextPage":false,"endCursor":"Y3Vyc29yOjM="}}}}}"#)
+            .create();
+
+        let client = client(&server);
+        let numbers: Result<Vec<_>, _> = client
+            .paginate::<serde_json::Value>(
+                ISSUES_QUERY,
+                json!({ "owner": "cli", "endCursor": "Y3Vyc29yOjI=" }),
+                "repository.issues",
+            )
+            .map(|issue| issue.map(|issue| issue["number"].clone()))
+            .collect();
+
+        assert_eq!(numbers, Ok(vec![json!(3)]));
+        mock.assert();
+    }
+
+    #[test]
+    fn paginate_reads_edges() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/graphql")
+            .with_body(r#"{"data":{"viewer":{"repositories":{"edges":[{"node":{"nameWithOwner":"cli/cli"}}],"pageInfo":{"hasNextPage":false,"endCursor":null}}}}}"#)
+            .create();
+
+        let repos: Result<Vec<Repository>, _> = client(&server)
+            .paginate(
+                "query($endCursor: String) { ... }",
+                (),
+                "viewer.repositories",
+            )
+            .collect();
+
+        assert_eq!(
+            repos,
+            Ok(vec![Repository {
+                name_with_owner: "cli/cli".to_owned()
+            }])
+        );
+    }
+
+    #[test]
+    fn paginate_stops_after_an_error() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/graphql")
+            .with_body(r#"{"data":null,"errors":[{"message":"Field 'issues' doesn't exist"}]}"#)
+            .create();
+
+        let client = client(&server);
+        let mut issues =
+            client.paginate::<serde_json::Value>(ISSUES_QUERY, json!({}), "repository.issues");
+
+        assert!(matches!(issues.next(), Some(Err(ClientError::GraphQL(_)))));
+        assert!(issues.next().is_none());
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_query_deserializes_data() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/graphql")
+            .match_header("authorization", "token ghp_xxxx")
+            .with_body(r#"{"data":{"repository":{"nameWithOwner":"cli/cli"}}}"#)
+            .create_async()
+            .await;
+
+        let client = AsyncGraphQLClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .base_url(&server.url()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            client
+                .query::<_, Data>(QUERY, &json!({ "owner": "cli", "name": "cli" }))
+                .await,
+            Ok(Data {
+                repository: Repository {
+                    name_with_owner: "cli/cli".to_owned()
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,298 @@
+const GITHUB: &str = "github.com";
+const GARAGE: &str = "garage.github.com";
+const LOCALHOST: &str = "github.localhost";
+const TENANCY: &str = "ghe.com";
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum HostKind {
+    // github.com and its subdomains, e.g. api.github.com
+    GitHub,
+    // GitHub's internal staging instance, garage.github.com
+    Garage,
+    // A development instance, github.localhost
+    Localhost,
+    // A GHEC data residency tenant, e.g. tenant.ghe.com
+    Tenancy,
+    // Anything else is assumed to be a GHES instance
+    Enterprise,
+}
+
+pub fn host_kind(host: &str) -> HostKind {
+    if host.eq_ignore_ascii_case(GARAGE) {
+        return HostKind::Garage;
+    }
+
+    match normalize_hostname(host).as_str() {
+        GITHUB => HostKind::GitHub,
+        LOCALHOST => HostKind::Localhost,
+        normalized if normalized.ends_with(&format!(".{TENANCY}")) => HostKind::Tenancy,
+        _ => HostKind::Enterprise,
+    }
+}
+
+// Mirrors go-gh's auth.IsEnterprise
+pub fn is_enterprise(host: &str) -> bool {
+    host_kind(host) == HostKind::Enterprise
+}
+
+// Mirrors go-gh's auth.IsTenancy
+pub fn is_tenancy(host: &str) -> bool {
+    host_kind(host) == HostKind::Tenancy
+}
+
+// Mirrors go-gh's auth.normalizeHostname, collapsing subdomains of github.com and
+// github.localhost, and anything deeper than a tenant on ghe.com.
+pub(crate) fn normalize_hostname(host: &str) -> String {
+    let hostname = host.to_ascii_lowercase();
+
+    if hostname.ends_with(&format!(".{GITHUB}")) {
+        return GITHUB.to_owned();
+    }
+
+    if hostname.ends_with(&format!(".{LOCALHOST}")) {
+        return LOCALHOST.to_owned();
+    }
+
+    if let Some(before) = hostname.strip_suffix(&format!(".{TENANCY}")) {
+        let tenant = before.rsplit('.').next().unwrap_or(before);
+        return format!("{tenant}.{TENANCY}");
+    }
+
+    hostname
+}
+
+// A normalized hostname, e.g. both "https://API.GitHub.com:443/" and "git@github.com"
+// become "github.com".
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct Host(String);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseHostError {
+    Empty,
+    InvalidCharacter(char),
+}
+
+impl std::fmt::Display for ParseHostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "host must not be empty"),
+            Self::InvalidCharacter(c) => write!(f, "host contains invalid character {c:?}"),
+        }
+    }
+}
+
+impl Host {
+    pub fn github() -> Self {
+        Self(GITHUB.to_owned())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn kind(&self) -> HostKind {
+        host_kind(&self.0)
+    }
+
+    // Mirrors gh's ghinstance.RESTPrefix, always ending in a slash.
+    pub fn rest_url(&self) -> String {
+        match self.kind() {
+            HostKind::Garage | HostKind::Enterprise => format!("https://{}/api/v3/", self.0),
+            HostKind::Localhost => format!("http://api.{}/", self.0),
+            HostKind::GitHub | HostKind::Tenancy => format!("https://api.{}/", self.0),
+        }
+    }
+
+    // Mirrors gh's ghinstance.GraphQLEndpoint.
+    pub fn graphql_url(&self) -> String {
+        match self.kind() {
+            HostKind::Garage | HostKind::Enterprise => format!("https://{}/api/graphql", self.0),
+            HostKind::Localhost => format!("http://api.{}/graphql", self.0),
+            HostKind::GitHub | HostKind::Tenancy => format!("https://api.{}/graphql", self.0),
+        }
+    }
+}
+
+impl std::str::FromStr for Host {
+    type Err = ParseHostError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let without_scheme = input.split_once("://").map_or(input, |(_, rest)| rest);
+        let authority = without_scheme.split('/').next().unwrap_or_default();
+        let without_user = authority.rsplit('@').next().unwrap_or_default();
+        let hostname = without_user
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches('.')
+            .to_ascii_lowercase();
+
+        if hostname.is_empty() {
+            return Err(ParseHostError::Empty);
+        }
+
+        if let Some(invalid) = hostname
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || *c == '-' || *c == '.'))
+        {
+            return Err(ParseHostError::InvalidCharacter(invalid));
+        }
+
+        if hostname == GARAGE {
+            return Ok(Self(GARAGE.to_owned()));
+        }
+
+        // The api. prefix is only dropped for the hosts known to have one, as a GHES
+        // instance may well be called e.g. api.corp.example.
+        Ok(Self(normalize_hostname(&hostname)))
+    }
+}
+
+impl std::fmt::Display for Host {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl AsRef<str> for Host {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_kind_classifies_hosts() {
+        assert_eq!(host_kind("github.com"), HostKind::GitHub);
+        assert_eq!(host_kind("api.github.com"), HostKind::GitHub);
+        assert_eq!(host_kind("GitHub.com"), HostKind::GitHub);
+        assert_eq!(host_kind("garage.github.com"), HostKind::Garage);
+        assert_eq!(host_kind("github.localhost"), HostKind::Localhost);
+        assert_eq!(host_kind("api.github.localhost"), HostKind::Localhost);
+        assert_eq!(host_kind("tenant.ghe.com"), HostKind::Tenancy);
+        assert_eq!(host_kind("api.tenant.ghe.com"), HostKind::Tenancy);
+        assert_eq!(host_kind("ghe.com"), HostKind::Enterprise);
+        assert_eq!(host_kind("my.ghes.com"), HostKind::Enterprise);
+    }
+
+    #[test]
+    fn is_enterprise_excludes_github_localhost_and_tenancy() {
+        assert!(!is_enterprise("github.com"));
+        assert!(!is_enterprise("garage.github.com"));
+        assert!(!is_enterprise("github.localhost"));
+        assert!(!is_enterprise("tenant.ghe.com"));
+        assert!(is_enterprise("my.ghes.com"));
+    }
+
+    #[test]
+    fn is_tenancy_matches_only_ghe_com_subdomains() {
+        assert!(is_tenancy("tenant.ghe.com"));
+        assert!(is_tenancy("Tenant.GHE.com"));
+        assert!(!is_tenancy("ghe.com"));
+        assert!(!is_tenancy("github.com"));
+        assert!(!is_tenancy("my.ghes.com"));
+    }
+
+    #[test]
+    fn normalize_hostname_collapses_subdomains() {
+        assert_eq!(normalize_hostname("api.github.com"), "github.com");
+        assert_eq!(
+            normalize_hostname("api.github.localhost"),
+            "github.localhost"
+        );
+        assert_eq!(normalize_hostname("api.tenant.ghe.com"), "tenant.ghe.com");
+        assert_eq!(normalize_hostname("My.GHES.com"), "my.ghes.com");
+    }
+
+    #[test]
+    fn host_parses_and_normalizes_input() {
+        for input in [
+            "github.com",
+            "GitHub.com",
+            "api.github.com",
+            "github.com:443",
+            "https://github.com/",
+            "https://api.github.com/user",
+            "git@github.com:owner/repo.git",
+            "ssh://git@github.com/owner/repo.git",
+            " github.com. ",
+        ] {
+            assert_eq!(
+                input.parse::<Host>().map(|host| host.to_string()),
+                Ok("github.com".to_owned()),
+                "{input}"
+            );
+        }
+    }
+
+    #[test]
+    fn host_keeps_enterprise_tenancy_and_garage_hosts() {
+        assert_eq!(
+            "https://My.GHES.com/api/v3".parse::<Host>(),
+            Ok(Host("my.ghes.com".to_owned()))
+        );
+        assert_eq!(
+            "api.tenant.ghe.com".parse::<Host>(),
+            Ok(Host("tenant.ghe.com".to_owned()))
+        );
+        assert_eq!(
+            "garage.github.com".parse::<Host>(),
+            Ok(Host("garage.github.com".to_owned()))
+        );
+        assert_eq!(
+            "https://api.corp.example/api/v3".parse::<Host>(),
+            Ok(Host("api.corp.example".to_owned()))
+        );
+    }
+
+    #[test]
+    fn host_rejects_empty_and_invalid_input() {
+        assert_eq!("".parse::<Host>(), Err(ParseHostError::Empty));
+        assert_eq!("https://".parse::<Host>(), Err(ParseHostError::Empty));
+        assert_eq!(
+            "my ghes.com".parse::<Host>(),
+            Err(ParseHostError::InvalidCharacter(' '))
+        );
+    }
+
+    #[test]
+    fn host_rest_url_depends_on_kind() {
+        let rest_url = |host: &str| host.parse::<Host>().unwrap().rest_url();
+
+        assert_eq!(rest_url("github.com"), "https://api.github.com/");
+        assert_eq!(rest_url("tenant.ghe.com"), "https://api.tenant.ghe.com/");
+        assert_eq!(rest_url("github.localhost"), "http://api.github.localhost/");
+        assert_eq!(
+            rest_url("garage.github.com"),
+            "https://garage.github.com/api/v3/"
+        );
+        assert_eq!(rest_url("my.ghes.com"), "https://my.ghes.com/api/v3/");
+    }
+
+    #[test]
+    fn host_graphql_url_depends_on_kind() {
+        let graphql_url = |host: &str| host.parse::<Host>().unwrap().graphql_url();
+
+        assert_eq!(graphql_url("github.com"), "https://api.github.com/graphql");
+        assert_eq!(
+            graphql_url("tenant.ghe.com"),
+            "https://api.tenant.ghe.com/graphql"
+        );
+        assert_eq!(
+            graphql_url("github.localhost"),
+            "http://api.github.localhost/graphql"
+        );
+        assert_eq!(
+            graphql_url("garage.github.com"),
+            "https://garage.github.com/api/graphql"
+        );
+        assert_eq!(
+            graphql_url("my.ghes.com"),
+            "https://my.ghes.com/api/graphql"
+        );
+    }
+}
@@ -0,0 +1,294 @@
+use reqwest::{header::HeaderMap, StatusCode};
+use serde::Deserialize;
+
+use crate::{host::Host, validate::implies, RateLimit};
+
+// An unsuccessful response from the API, mirroring go-gh's api.HTTPError.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HttpError {
+    pub status: u16,
+    pub url: String,
+    pub message: String, // empty when the body isn't the usual JSON error
+    pub documentation_url: Option<String>,
+    pub errors: Vec<HttpErrorItem>,
+    pub headers: HeaderMap,
+}
+
+// One entry of the errors array, e.g. a validation failure for a single field.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HttpErrorItem {
+    pub resource: String,
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+// Some endpoints list plain strings rather than objects.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawErrorItem {
+    Message(String),
+    Item(HttpErrorItem),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct Body {
+    message: String,
+    documentation_url: Option<String>,
+    errors: Vec<RawErrorItem>,
+}
+
+impl HttpError {
+    pub(crate) fn new(status: StatusCode, url: &str, headers: HeaderMap, body: &str) -> Self {
+        let body = serde_json::from_str::<Body>(body).unwrap_or_default();
+
+        Self {
+            status: status.as_u16(),
+            url: url.to_owned(),
+            message: body.message,
+            documentation_url: body.documentation_url,
+            errors: body
+                .errors
+                .into_iter()
+                .map(|item| match item {
+                    RawErrorItem::Message(message) => HttpErrorItem {
+                        message,
+                        ..HttpErrorItem::default()
+                    },
+                    RawErrorItem::Item(item) => item,
+                })
+                .collect(),
+            headers,
+        }
+    }
+
+    // Quote this when reporting problems to GitHub.
+    pub fn request_id(&self) -> Option<&str> {
+        self.header("X-GitHub-Request-Id")
+    }
+
+    // The rate limit as of this response, unlike the clients' rate_limit which may have
+    // moved on to a later one.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        RateLimit::from_headers(&self.headers)
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status == StatusCode::NOT_FOUND.as_u16()
+    }
+
+    // When the request failed for lack of an OAuth scope, how to get it, like gh's
+    // api.ScopesSuggestion.
+    pub fn scopes_suggestion(&self) -> Option<String> {
+        if !(400..500).contains(&self.status)
+            || self.status == StatusCode::UNPROCESSABLE_ENTITY.as_u16()
+        {
+            return None;
+        }
+
+        // Tokens without OAuth scopes, e.g. fine-grained PATs, don't send this, and like gh
+        // we don't second guess tokens that send it empty either.
+        let granted: Vec<_> = self
+            .header("X-OAuth-Scopes")
+            .filter(|granted| !granted.trim().is_empty())?
+            .split(',')
+            .map(str::trim)
+            .collect();
+
+        // Any one of the accepted scopes will do, so only suggest them when none is granted.
+        let accepted = self.header("X-Accepted-OAuth-Scopes").unwrap_or_default();
+        if accepted.split(',').map(str::trim).any(|scope| {
+            scope.is_empty()
+                || granted
+                    .iter()
+                    .any(|granted| *granted == scope || implies(granted, scope))
+        }) {
+            return None;
+        }
+
+        let host = self
+            .url
+            .parse::<Host>()
+            .map(|host| host.to_string())
+            .unwrap_or_default();
+
+        Some(format!(
+            "This API operation needs the \"{accepted}\" scope. To request it, run:  gh auth refresh -h {host} -s {accepted}"
+        ))
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self.message.as_str() {
+            "" => StatusCode::from_u16(self.status)
+                .ok()
+                .and_then(|status| status.canonical_reason())
+                .unwrap_or_default(),
+            message => message,
+        };
+        write!(f, "HTTP {}: {message} ({})", self.status, self.url)?;
+
+        for item in &self.errors {
+            match item.message.as_str() {
+                "" => write!(f, "\n{}.{} {}", item.resource, item.field, item.code)?,
+                message => write!(f, "\n{message}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn http_error(status: u16, headers: &[(&'static str, &'static str)], body: &str) -> HttpError {
+        HttpError::new(
+            StatusCode::from_u16(status).unwrap(),
+            "https://api.github.com/repos/cli/cli/issues",
+            headers
+                .iter()
+                .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+                .collect(),
+            body,
+        )
+    }
+
+    #[test]
+    fn new_parses_error_body() {
+        let err = http_error(
+            422,
+            &[("x-github-request-id", "0400:1F2E:3D4C:5B6A")],
+            r#"{"message":"Validation Failed","errors":[{"resource":"Issue","field":"title","code":"missing_field"},"Label does not exist"],"documentation_url":"https://docs.github.com/rest/issues/issues#create-an-issue"}"#,
+        );
+
+        assert_eq!(err.message, "Validation Failed");
+        assert_eq!(
+            err.documentation_url.as_deref(),
+            Some("https://docs.github.com/rest/issues/issues#create-an-issue")
+        );
+        assert_eq!(err.request_id(), Some("0400:1F2E:3D4C:5B6A"));
+        assert_eq!(
+            err.to_string(),
+            "HTTP 422: Validation Failed (https://api.github.com/repos/cli/cli/issues)\nIssue.title missing_field\nLabel does not exist"
+        );
+    }
+
+    #[test]
+    fn display_falls_back_to_status_reason() {
+        let err = http_error(502, &[], "<html>Bad Gateway</html>");
+
+        assert_eq!(err.message, "");
+        assert_eq!(
+            err.to_string(),
+            "HTTP 502: Bad Gateway (https://api.github.com/repos/cli/cli/issues)"
+        );
+    }
+
+    #[test]
+    fn rate_limit_is_read_from_the_response() {
+        let err = http_error(
+            403,
+            &[
+                ("x-ratelimit-limit", "5000"),
+                ("x-ratelimit-remaining", "0"),
+            ],
+            r#"{"message":"API rate limit exceeded"}"#,
+        );
+
+        assert_eq!(
+            err.rate_limit().and_then(|rate_limit| rate_limit.remaining),
+            Some(0)
+        );
+        assert_eq!(http_error(404, &[], "").rate_limit(), None);
+    }
+
+    #[test]
+    fn scopes_suggestion_names_missing_scope() {
+        let err = http_error(
+            404,
+            &[
+                ("x-oauth-scopes", "gist, read:org"),
+                ("x-accepted-oauth-scopes", "repo"),
+            ],
+            r#"{"message":"Not Found"}"#,
+        );
+
+        assert!(err.is_not_found());
+        assert_eq!(
+            err.scopes_suggestion().as_deref(),
+            Some(
+                "This API operation needs the \"repo\" scope. To request it, run:  gh auth refresh -h github.com -s repo"
+            )
+        );
+    }
+
+    #[test]
+    fn scopes_suggestion_is_none_when_scopes_are_granted() {
+        let suggestion = |accepted| {
+            http_error(
+                403,
+                &[
+                    ("x-oauth-scopes", "repo, admin:org"),
+                    ("x-accepted-oauth-scopes", accepted),
+                ],
+                "",
+            )
+            .scopes_suggestion()
+        };
+
+        assert_eq!(suggestion("read:org"), None);
+        assert_eq!(suggestion("public_repo"), None);
+        assert_eq!(suggestion("security_events"), None);
+        assert_eq!(suggestion(""), None);
+        assert_eq!(http_error(403, &[], "").scopes_suggestion(), None);
+        assert_eq!(
+            http_error(
+                403,
+                &[("x-oauth-scopes", ""), ("x-accepted-oauth-scopes", "repo")],
+                ""
+            )
+            .scopes_suggestion(),
+            None
+        );
+    }
+
+    #[test]
+    fn scopes_suggestion_is_none_when_any_accepted_scope_is_granted() {
+        let suggestion = |granted| {
+            http_error(
+                403,
+                &[
+                    ("x-oauth-scopes", granted),
+                    (
+                        "x-accepted-oauth-scopes",
+                        "admin:org, read:org, repo, user, write:org",
+                    ),
+                ],
+                "",
+            )
+            .scopes_suggestion()
+        };
+
+        assert_eq!(suggestion("read:org"), None);
+        assert_eq!(suggestion("gist, user"), None);
+        assert_eq!(
+            suggestion("gist").as_deref(),
+            Some(
+                "This API operation needs the \"admin:org, read:org, repo, user, write:org\" scope. To request it, run:  gh auth refresh -h github.com -s admin:org, read:org, repo, user, write:org"
+            )
+        );
+    }
+}
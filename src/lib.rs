@@ -1,5 +1,45 @@
 use core::str;
-use std::{process::Command, str::Utf8Error};
+use std::{
+    collections::BTreeSet,
+    ffi::{OsStr, OsString},
+    str::Utf8Error,
+    sync::Arc,
+};
+
+mod cache;
+mod client;
+mod command;
+mod config;
+mod graphql;
+mod host;
+mod http_error;
+mod rate_limit;
+mod repository;
+mod rest;
+mod secret;
+#[cfg(all(feature = "native-keyring", target_os = "linux"))]
+mod secret_service;
+mod token_kind;
+mod validate;
+
+pub use client::{ClientError, ClientOptions, GraphQLErrorItem, GraphQLErrorLocation};
+pub use command::{CommandOutput, CommandRunner, ProcessRunner};
+pub use config::TokenFromConfigError;
+#[cfg(feature = "async")]
+pub use graphql::{AsyncGraphQLClient, AsyncGraphQLPaginate};
+pub use graphql::{GraphQLClient, GraphQLPaginate};
+pub use host::{host_kind, is_enterprise, is_tenancy, Host, HostKind, ParseHostError};
+pub use http_error::{HttpError, HttpErrorItem};
+pub use rate_limit::{RateLimit, RateLimitPolicy};
+pub use repository::{
+    current_repository, CurrentRepositoryError, ParseRepositoryError, Repository,
+};
+#[cfg(feature = "async")]
+pub use rest::{AsyncPaginate, AsyncRestClient};
+pub use rest::{Paginate, RestClient};
+pub use secret::SecretString;
+pub use token_kind::TokenKind;
+pub use validate::{TokenInfo, ValidateError};
 
 #[derive(Debug, PartialEq, Eq)]
 pub enum Source {
@@ -8,6 +48,24 @@ pub enum Source {
     Keyring,
 }
 
+// Identifies a Source without its details, for choosing where TokenResolver looks.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum SourceKind {
+    Env,
+    Config,
+    Keyring,
+}
+
+impl Source {
+    pub fn kind(&self) -> SourceKind {
+        match self {
+            Self::Env(_) => SourceKind::Env,
+            Self::Config(_) => SourceKind::Config,
+            Self::Keyring => SourceKind::Keyring,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum Var {
     GHToken,
@@ -16,10 +74,39 @@ pub enum Var {
     GitHubEnterpriseToken,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(PartialEq, Eq)]
 pub struct Token {
-    pub value: String,
+    pub value: SecretString,
     pub source: Source,
+    pub user: Option<String>, // unknown for env tokens
+}
+
+// Neither Debug nor Display print the secret, so tokens can be logged safely.
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Token")
+            .field("value", &self.value)
+            .field("source", &self.source)
+            .field("user", &self.user)
+            .finish()
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+#[cfg(test)]
+impl Token {
+    pub(crate) fn new(value: &str, source: Source) -> Self {
+        Self {
+            value: value.into(),
+            source,
+            user: None,
+        }
+    }
 }
 
 impl From<EnvToken> for Token {
@@ -27,6 +114,7 @@ impl From<EnvToken> for Token {
         Self {
             value: env_token.value,
             source: Source::Env(env_token.var),
+            user: None,
         }
     }
 }
@@ -36,6 +124,7 @@ impl From<ConfigToken> for Token {
         Self {
             value: config_token.value,
             source: Source::Config(config_token.path),
+            user: config_token.user,
         }
     }
 }
@@ -45,39 +134,410 @@ impl From<KeyringToken> for Token {
         Self {
             value: keyring_token.value,
             source: Source::Keyring,
+            user: keyring_token.user,
         }
     }
 }
 
 struct EnvToken {
-    value: String,
+    value: SecretString,
     var: Var,
 }
 
 struct ConfigToken {
-    value: String,
+    value: SecretString,
     path: String,
+    user: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 struct KeyringToken {
-    value: String,
+    value: SecretString,
+    user: Option<String>,
 }
 
-pub fn token_for_host(host: &str) -> Option<Token> {
-    token_from_env(host)
-        .map(Token::from)
-        .or_else(|| token_from_config(host).map(Token::from))
-        .or_else(|| {
-            token_from_keyring(host)
-                .map_err(|err| panic!("{err:?}"))
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenError {
+    InvalidHost(ParseHostError),
+    Config(TokenFromConfigError),
+    Keyring(TokenFromKeyringError),
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHost(err) => err.fmt(f),
+            Self::Config(err) => err.fmt(f),
+            Self::Keyring(TokenFromKeyringError::FailToExecute(kind)) => {
+                write!(f, "failed to execute gh: {kind}")
+            }
+            Self::Keyring(TokenFromKeyringError::StdoutNotUTF8(err)) => {
+                write!(f, "gh auth token wrote invalid UTF-8 to stdout: {err}")
+            }
+            Self::Keyring(TokenFromKeyringError::StdErrorNotUTF8(err)) => {
+                write!(f, "gh auth token wrote invalid UTF-8 to stderr: {err}")
+            }
+            Self::Keyring(TokenFromKeyringError::OutputStatusFail(stderr)) => {
+                write!(f, "gh auth token failed: {}", stderr.trim())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+impl From<ParseHostError> for TokenError {
+    fn from(err: ParseHostError) -> Self {
+        Self::InvalidHost(err)
+    }
+}
+
+impl From<TokenFromConfigError> for TokenError {
+    fn from(err: TokenFromConfigError) -> Self {
+        Self::Config(err)
+    }
+}
+
+impl From<TokenFromKeyringError> for TokenError {
+    fn from(err: TokenFromKeyringError) -> Self {
+        Self::Keyring(err)
+    }
+}
+
+// Accepts anything that can be parsed as a Host, e.g. "github.com", "api.github.com"
+// or "https://github.com/".
+pub fn token_for_host(host: &str) -> Result<Option<Token>, TokenError> {
+    TokenResolver::new().token_for_host(host)
+}
+
+// Looks up the token for a specific account rather than the active one, like
+// `gh auth token --user`. Env tokens aren't tied to an account so are never used.
+pub fn token_for_host_and_user(host: &str, user: &str) -> Result<Option<Token>, TokenError> {
+    TokenResolver::new().token_for_host_and_user(host, user)
+}
+
+// Mirrors go-gh's auth.KnownHosts, except that hosts we can't find a token for are
+// left out, so every entry is one the user is actually authenticated with.
+pub fn known_hosts() -> Result<Vec<KnownHost>, TokenError> {
+    TokenResolver::new().known_hosts()
+}
+
+// Like token_for_host, but runs gh without blocking. Needs a tokio runtime.
+#[cfg(feature = "async")]
+pub async fn token_for_host_async(host: &str) -> Result<Option<Token>, TokenError> {
+    TokenResolver::new().token_for_host_async(host).await
+}
+
+#[cfg(feature = "async")]
+pub async fn token_for_host_and_user_async(
+    host: &str,
+    user: &str,
+) -> Result<Option<Token>, TokenError> {
+    TokenResolver::new()
+        .token_for_host_and_user_async(host, user)
+        .await
+}
+
+// Configures how tokens are resolved, for when the free functions' defaults don't fit.
+//
+// TokenResolver::new()
+//     .sources([SourceKind::Keyring])
+//     .gh_path("/opt/gh/bin/gh")
+//     .token_for_host("github.com")
+#[derive(Clone)]
+pub struct TokenResolver {
+    sources: Vec<SourceKind>,
+    gh_path: Option<OsString>,
+    runner: Arc<dyn CommandRunner>,
+    native_keyring: bool,
+}
+
+impl Default for TokenResolver {
+    fn default() -> Self {
+        Self {
+            sources: vec![SourceKind::Env, SourceKind::Config, SourceKind::Keyring],
+            gh_path: None,
+            runner: Arc::new(ProcessRunner),
+            native_keyring: true,
+        }
+    }
+}
+
+impl std::fmt::Debug for TokenResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenResolver")
+            .field("sources", &self.sources)
+            .field("gh_path", &self.gh_path)
+            .field("native_keyring", &self.native_keyring)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The sources to look for a token in, in order. Defaults to env, config, then keyring,
+    // and sources left out are never consulted.
+    pub fn sources(mut self, sources: impl IntoIterator<Item = SourceKind>) -> Self {
+        self.sources = sources.into_iter().collect();
+        self
+    }
+
+    // The gh executable used for keyring lookups. Defaults to GH_PATH, which gh sets when
+    // running an extension, and then to whichever gh is on the PATH.
+    pub fn gh_path(mut self, gh_path: impl Into<OsString>) -> Self {
+        self.gh_path = Some(gh_path.into());
+        self
+    }
+
+    // How gh is executed for keyring lookups, and git for current_repository. Defaults to
+    // ProcessRunner.
+    pub fn runner(mut self, runner: impl CommandRunner + 'static) -> Self {
+        self.runner = Arc::new(runner);
+        self
+    }
+
+    // Whether keyring lookups read the Secret Service directly before asking gh. Defaults
+    // to true, and only has an effect with the native-keyring feature on Linux.
+    pub fn native_keyring(mut self, native_keyring: bool) -> Self {
+        self.native_keyring = native_keyring;
+        self
+    }
+
+    pub fn token_for_host(&self, host: &str) -> Result<Option<Token>, TokenError> {
+        self.token_for(&host.parse()?, None)
+    }
+
+    pub fn token_for_host_and_user(
+        &self,
+        host: &str,
+        user: &str,
+    ) -> Result<Option<Token>, TokenError> {
+        self.token_for(&host.parse()?, Some(user))
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn token_for_host_async(&self, host: &str) -> Result<Option<Token>, TokenError> {
+        self.token_for_async(&host.parse()?, None).await
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn token_for_host_and_user_async(
+        &self,
+        host: &str,
+        user: &str,
+    ) -> Result<Option<Token>, TokenError> {
+        self.token_for_async(&host.parse()?, Some(user)).await
+    }
+
+    pub fn known_hosts(&self) -> Result<Vec<KnownHost>, TokenError> {
+        self.configured_hosts()?
+            .into_iter()
+            .filter_map(|host| {
+                self.token_for(&host, None)
+                    .map(|token| {
+                        token.map(|token| KnownHost {
+                            host,
+                            source: token.source,
+                        })
+                    })
+                    .transpose()
+            })
+            .collect()
+    }
+
+    // Like the free current_repository, but runs git with the resolver's runner and only
+    // knows the hosts its sources do.
+    pub fn current_repository(&self) -> Result<Repository, CurrentRepositoryError> {
+        repository::current_repository_with(self)
+    }
+
+    fn token_for(&self, host: &Host, user: Option<&str>) -> Result<Option<Token>, TokenError> {
+        for kind in &self.sources {
+            let token = match kind {
+                // Env tokens aren't tied to an account, so only stand in for the active one.
+                SourceKind::Env if user.is_some() => None,
+                SourceKind::Env => token_from_env(host).map(Token::from),
+                SourceKind::Config => token_from_config(host, user)?.map(Token::from),
+                SourceKind::Keyring => self.keyring_token(host, user)?,
+            };
+
+            if token.is_some() {
+                return Ok(token);
+            }
+        }
+
+        Ok(None)
+    }
+
+    // The same as token_for, except for running gh asynchronously. hosts.yml is small
+    // and local, so it's still read synchronously.
+    #[cfg(feature = "async")]
+    async fn token_for_async(
+        &self,
+        host: &Host,
+        user: Option<&str>,
+    ) -> Result<Option<Token>, TokenError> {
+        for kind in &self.sources {
+            let token = match kind {
+                SourceKind::Env if user.is_some() => None,
+                SourceKind::Env => token_from_env(host).map(Token::from),
+                SourceKind::Config => token_from_config(host, user)?.map(Token::from),
+                SourceKind::Keyring => self.keyring_token_async(host, user).await?,
+            };
+
+            if token.is_some() {
+                return Ok(token);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn keyring_token(&self, host: &Host, user: Option<&str>) -> Result<Option<Token>, TokenError> {
+        let keyring_token = match self.secret_service_token(host, user) {
+            Some(keyring_token) => Some(keyring_token),
+            None => token_from_keyring(self.runner.as_ref(), &self.gh(), host, user)?,
+        };
+        with_config_user(host, keyring_token, self.reads_config())
+    }
+
+    #[cfg(feature = "async")]
+    async fn keyring_token_async(
+        &self,
+        host: &Host,
+        user: Option<&str>,
+    ) -> Result<Option<Token>, TokenError> {
+        // The Secret Service is a blocking D-Bus call.
+        #[cfg(all(feature = "native-keyring", target_os = "linux"))]
+        let secret = {
+            let (secret_host, secret_user) = (host.clone(), user.map(str::to_owned));
+            let resolver = self.clone();
+            tokio::task::spawn_blocking(move || {
+                resolver.secret_service_token(&secret_host, secret_user.as_deref())
+            })
+            .await
+            .ok()
+            .flatten()
+        };
+        #[cfg(not(all(feature = "native-keyring", target_os = "linux")))]
+        let secret = self.secret_service_token(host, user);
+
+        let keyring_token = match secret {
+            Some(keyring_token) => Some(keyring_token),
+            None => token_from_keyring_async(self.runner.as_ref(), &self.gh(), host, user).await?,
+        };
+        with_config_user(host, keyring_token, self.reads_config())
+    }
+
+    // None when the Secret Service wasn't asked, couldn't answer (e.g. there's no
+    // session bus) or has no token for the host, in which case gh is asked instead.
+    fn secret_service_token(&self, host: &Host, user: Option<&str>) -> Option<KeyringToken> {
+        #[cfg(all(feature = "native-keyring", target_os = "linux"))]
+        if self.native_keyring {
+            return secret_service::token_from_secret_service(host, user)
                 .ok()
                 .flatten()
-                .map(Token::from)
+                .map(|value| KeyringToken {
+                    value,
+                    user: user.map(str::to_owned),
+                });
+        }
+
+        let _ = (host, user);
+        None
+    }
+
+    fn reads_config(&self) -> bool {
+        self.sources.contains(&SourceKind::Config)
+    }
+
+    // The hosts go-gh's auth.KnownHosts would list, without checking for a token for
+    // each. Only the sources the resolver uses are consulted.
+    fn configured_hosts(&self) -> Result<BTreeSet<Host>, TokenError> {
+        let mut hosts = BTreeSet::new();
+
+        if let Some(host) = gh_host() {
+            hosts.insert(host.parse::<Host>()?);
+        }
+
+        if self.sources.contains(&SourceKind::Env) && token_from_env(&Host::github()).is_some() {
+            hosts.insert(Host::github());
+        }
+
+        if self.reads_config() {
+            if let Some(config_hosts) = config::load_hosts()? {
+                hosts.extend(config_hosts.hosts.into_keys());
+            }
+        }
+
+        Ok(hosts)
+    }
+
+    fn gh(&self) -> OsString {
+        self.gh_path
+            .clone()
+            .or_else(|| std::env::var_os("GH_PATH").filter(|path| !path.is_empty()))
+            .unwrap_or_else(|| OsString::from("gh"))
+    }
+}
+
+// gh auth token doesn't tell us whose token it printed, so fall back to hosts.yml, as
+// long as the resolver is allowed to read it.
+fn with_config_user(
+    host: &Host,
+    keyring_token: Option<KeyringToken>,
+    config: bool,
+) -> Result<Option<Token>, TokenError> {
+    let Some(keyring_token) = keyring_token else {
+        return Ok(None);
+    };
+
+    let user = match keyring_token.user {
+        Some(user) => Some(user),
+        None if config => config::load_hosts()?
+            .and_then(|mut hosts| hosts.hosts.remove(host))
+            .and_then(|host_config| host_config.user),
+        None => None,
+    };
+
+    Ok(Some(
+        KeyringToken {
+            user,
+            ..keyring_token
+        }
+        .into(),
+    ))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Account {
+    pub user: String,
+    pub active: bool,
+}
+
+// Lists the accounts gh knows about for a host, as recorded in hosts.yml.
+pub fn accounts_for_host(host: &str) -> Result<Vec<Account>, TokenError> {
+    let host = host.parse::<Host>()?;
+
+    let Some(host_config) = config::load_hosts()?.and_then(|mut hosts| hosts.hosts.remove(&host))
+    else {
+        return Ok(Vec::new());
+    };
+
+    Ok(host_config
+        .usernames()
+        .into_iter()
+        .map(|user| Account {
+            active: host_config.user.as_ref() == Some(&user),
+            user,
         })
+        .collect())
 }
 
-fn token_from_env(host: &str) -> Option<EnvToken> {
+fn token_from_env(host: &Host) -> Option<EnvToken> {
     // First we load the tokens that might be in the environment
     struct EnvTokens {
         gh_token: Option<EnvToken>,
@@ -87,7 +547,10 @@ fn token_from_env(host: &str) -> Option<EnvToken> {
     }
 
     fn to_env_token(var: Var) -> impl Fn(String) -> EnvToken {
-        move |value| EnvToken { value, var }
+        move |value| EnvToken {
+            value: value.into(),
+            var,
+        }
     }
 
     // TODO: consider whether we should return an error here.
@@ -106,18 +569,35 @@ fn token_from_env(host: &str) -> Option<EnvToken> {
             .map(to_env_token(Var::GitHubEnterpriseToken)),
     };
 
-    match host {
-        // TODO: do GHEC and localhost
-        "github.com" => env_tokens.gh_token.or(env_tokens.github_token),
-        _ => env_tokens
+    match host.kind() {
+        HostKind::Enterprise => env_tokens
             .gh_enterprise_token
             .or(env_tokens.github_enterprise_token),
+        HostKind::GitHub | HostKind::Garage | HostKind::Localhost | HostKind::Tenancy => {
+            env_tokens.gh_token.or(env_tokens.github_token)
+        }
     }
 }
 
-// TODO
-fn token_from_config(_: &str) -> Option<ConfigToken> {
-    None
+fn token_from_config(
+    host: &Host,
+    user: Option<&str>,
+) -> Result<Option<ConfigToken>, TokenFromConfigError> {
+    config::load_hosts().map(|hosts| {
+        hosts.and_then(|mut hosts| {
+            let mut host_config = hosts.hosts.remove(host)?;
+            let (value, user) = match user {
+                Some(user) => (host_config.user_token(user)?, Some(user.to_owned())),
+                None => (host_config.oauth_token?, host_config.user),
+            };
+
+            Some(ConfigToken {
+                value,
+                path: hosts.path,
+                user,
+            })
+        })
+    })
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -128,30 +608,56 @@ pub enum TokenFromKeyringError {
     OutputStatusFail(String),
 }
 
-fn token_from_keyring(host: &str) -> Result<Option<KeyringToken>, TokenFromKeyringError> {
-    let args;
+fn token_from_keyring(
+    runner: &dyn CommandRunner,
+    gh: &OsStr,
+    host: &Host,
+    user: Option<&str>,
+) -> Result<Option<KeyringToken>, TokenFromKeyringError> {
+    keyring_output(runner.run(gh, &keyring_args(host, user)), user)
+}
+
+#[cfg(feature = "async")]
+async fn token_from_keyring_async(
+    runner: &dyn CommandRunner,
+    gh: &OsStr,
+    host: &Host,
+    user: Option<&str>,
+) -> Result<Option<KeyringToken>, TokenFromKeyringError> {
+    keyring_output(runner.run_async(gh, &keyring_args(host, user)).await, user)
+}
 
-    #[cfg(test)]
-    {
-        args = ["auth", "token", "--hostname", host];
-    }
+fn keyring_args<'a>(host: &'a Host, user: Option<&'a str>) -> Vec<&'a str> {
+    let mut args = vec![
+        "auth",
+        "token",
+        "--secure-storage",
+        "--hostname",
+        host.as_str(),
+    ];
 
-    #[cfg(not(test))]
-    {
-        args = ["auth", "token", "--secure-storage", "--hostname", host];
+    if let Some(user) = user {
+        args.extend(["--user", user]);
     }
 
-    Command::new("gh")
-        .args(args)
-        .output()
+    args
+}
+
+fn keyring_output(
+    output: std::io::Result<CommandOutput>,
+    user: Option<&str>,
+) -> Result<Option<KeyringToken>, TokenFromKeyringError> {
+    output
         .map_err(|err| TokenFromKeyringError::FailToExecute(err.kind()))
         .and_then(|output| {
-            if output.status.success() {
+            // CommandOutput wipes gh's raw output once it's dropped, leaving only the token.
+            if output.success {
                 str::from_utf8(&output.stdout)
                     .map_err(TokenFromKeyringError::StdoutNotUTF8)
                     .map(|value| {
                         Some(KeyringToken {
-                            value: value.trim().to_string(),
+                            value: value.trim().into(),
+                            user: user.map(str::to_owned),
                         })
                     })
             } else {
@@ -170,13 +676,100 @@ fn token_from_keyring(host: &str) -> Result<Option<KeyringToken>, TokenFromKeyri
         })
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum HostSource {
+    Env,            // GH_HOST
+    Config(String), // path to file
+    Default,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct DefaultHost {
+    pub host: Host,
+    pub source: HostSource,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DefaultHostError {
+    InvalidHost(ParseHostError),
+    Config(TokenFromConfigError),
+}
+
+impl std::fmt::Display for DefaultHostError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHost(err) => write!(f, "invalid GH_HOST: {err}"),
+            Self::Config(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for DefaultHostError {}
+
+// Mirrors go-gh's auth.DefaultHost: GH_HOST wins, then the only host in hosts.yml if
+// there is exactly one, and otherwise github.com.
+pub fn default_host() -> Result<DefaultHost, DefaultHostError> {
+    if let Some(host) = gh_host() {
+        return host
+            .parse()
+            .map(|host| DefaultHost {
+                host,
+                source: HostSource::Env,
+            })
+            .map_err(DefaultHostError::InvalidHost);
+    }
+
+    let hosts = config::load_hosts().map_err(DefaultHostError::Config)?;
+    if let Some(hosts) = hosts.filter(|hosts| hosts.hosts.len() == 1) {
+        if let Some(host) = hosts.hosts.into_keys().next() {
+            return Ok(DefaultHost {
+                host,
+                source: HostSource::Config(hosts.path),
+            });
+        }
+    }
+
+    Ok(DefaultHost {
+        host: "github.com"
+            .parse()
+            .map_err(DefaultHostError::InvalidHost)?,
+        source: HostSource::Default,
+    })
+}
+
+fn gh_host() -> Option<String> {
+    config::non_empty_var("GH_HOST")
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct KnownHost {
+    pub host: Host,
+    pub source: Source,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn token_for_host_returns_none_when_no_match() {
-        assert_eq!(token_for_host("unknown-host.com"), None)
+        let dir = tempfile::tempdir().unwrap();
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_ENTERPRISE_TOKEN", None),
+                ("GITHUB_ENTERPRISE_TOKEN", None),
+            ],
+            || {
+                assert_eq!(
+                    TokenResolver::new()
+                        .native_keyring(false)
+                        .runner(gh_fails_with(b"no oauth token found for unknown-host.com"))
+                        .token_for_host("unknown-host.com"),
+                    Ok(None)
+                )
+            },
+        );
     }
 
     #[test]
@@ -184,10 +777,11 @@ mod tests {
         temp_env::with_var("GH_TOKEN", Some("gh-token-value"), || {
             assert_eq!(
                 token_for_host("github.com"),
-                Some(Token {
-                    value: "gh-token-value".to_owned(),
-                    source: Source::Env(Var::GHToken)
-                }),
+                Ok(Some(Token {
+                    value: "gh-token-value".into(),
+                    source: Source::Env(Var::GHToken),
+                    user: None,
+                })),
             )
         });
     }
@@ -197,10 +791,11 @@ mod tests {
         temp_env::with_var("GITHUB_TOKEN", Some("github-token-value"), || {
             assert_eq!(
                 token_for_host("github.com"),
-                Some(Token {
-                    value: "github-token-value".to_owned(),
-                    source: Source::Env(Var::GitHubToken)
-                })
+                Ok(Some(Token {
+                    value: "github-token-value".into(),
+                    source: Source::Env(Var::GitHubToken),
+                    user: None,
+                }))
             )
         });
     }
@@ -215,10 +810,11 @@ mod tests {
             || {
                 assert_eq!(
                     token_for_host("github.com"),
-                    Some(Token {
-                        value: "gh-token-value".to_owned(),
-                        source: Source::Env(Var::GHToken)
-                    })
+                    Ok(Some(Token {
+                        value: "gh-token-value".into(),
+                        source: Source::Env(Var::GHToken),
+                        user: None,
+                    }))
                 )
             },
         );
@@ -232,10 +828,11 @@ mod tests {
             || {
                 assert_eq!(
                     token_for_host("my.ghes.com"),
-                    Some(Token {
-                        value: "gh-enterprise-token-value".to_owned(),
-                        source: Source::Env(Var::GHEnterpriseToken)
-                    })
+                    Ok(Some(Token {
+                        value: "gh-enterprise-token-value".into(),
+                        source: Source::Env(Var::GHEnterpriseToken),
+                        user: None,
+                    }))
                 )
             },
         );
@@ -249,10 +846,11 @@ mod tests {
             || {
                 assert_eq!(
                     token_for_host("my.ghes.com"),
-                    Some(Token {
-                        value: "github-enterprise-token-value".to_owned(),
-                        source: Source::Env(Var::GitHubEnterpriseToken)
-                    })
+                    Ok(Some(Token {
+                        value: "github-enterprise-token-value".into(),
+                        source: Source::Env(Var::GitHubEnterpriseToken),
+                        user: None,
+                    }))
                 )
             },
         );
@@ -271,24 +869,655 @@ mod tests {
             || {
                 assert_eq!(
                     token_for_host("my.ghes.com"),
-                    Some(Token {
-                        value: "gh-enterprise-token-value".to_owned(),
-                        source: Source::Env(Var::GHEnterpriseToken)
-                    })
+                    Ok(Some(Token {
+                        value: "gh-enterprise-token-value".into(),
+                        source: Source::Env(Var::GHEnterpriseToken),
+                        user: None,
+                    }))
                 )
             },
         );
     }
 
     #[test]
-    fn token_for_keyring_asks_for_token_from_gh() {
+    fn token_for_host_uses_gh_token_variable_for_tenancy_hosts() {
+        temp_env::with_vars(
+            [
+                ("GH_TOKEN", Some("gh-token-value")),
+                ("GH_ENTERPRISE_TOKEN", Some("gh-enterprise-token-value")),
+            ],
+            || {
+                assert_eq!(
+                    token_for_host("tenant.ghe.com"),
+                    Ok(Some(Token {
+                        value: "gh-token-value".into(),
+                        source: Source::Env(Var::GHToken),
+                        user: None,
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_for_host_uses_gh_token_variable_for_localhost() {
+        temp_env::with_vars(
+            [
+                ("GH_TOKEN", Some("gh-token-value")),
+                ("GH_ENTERPRISE_TOKEN", Some("gh-enterprise-token-value")),
+            ],
+            || {
+                assert_eq!(
+                    token_for_host("github.localhost"),
+                    Ok(Some(Token {
+                        value: "gh-token-value".into(),
+                        source: Source::Env(Var::GHToken),
+                        user: None,
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_for_host_normalizes_github_com_variants() {
         temp_env::with_var("GH_TOKEN", Some("gh-token-value"), || {
+            for host in [
+                "api.github.com",
+                "GitHub.com",
+                "https://github.com/",
+                "github.com:443",
+            ] {
+                assert_eq!(
+                    token_for_host(host),
+                    Ok(Some(Token {
+                        value: "gh-token-value".into(),
+                        source: Source::Env(Var::GHToken),
+                        user: None,
+                    })),
+                    "{host}"
+                )
+            }
+        });
+    }
+
+    #[test]
+    fn token_for_host_returns_error_for_invalid_host() {
+        assert_eq!(
+            token_for_host(""),
+            Err(TokenError::InvalidHost(ParseHostError::Empty))
+        )
+    }
+
+    #[test]
+    fn token_for_host_uses_oauth_token_from_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(&path, "My.GHES.com:\n    oauth_token: config-token-value\n").unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_ENTERPRISE_TOKEN", None),
+                ("GITHUB_ENTERPRISE_TOKEN", None),
+            ],
+            || {
+                assert_eq!(
+                    token_for_host("https://my.ghes.com/"),
+                    Ok(Some(Token {
+                        value: "config-token-value".into(),
+                        source: Source::Config(path.to_string_lossy().into_owned()),
+                        user: None,
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_for_host_prefers_env_over_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("hosts.yml"),
+            "github.com:\n    oauth_token: config-token-value\n",
+        )
+        .unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_TOKEN", Some("gh-token-value".as_ref())),
+            ],
+            || {
+                assert_eq!(
+                    token_for_host("github.com"),
+                    Ok(Some(Token {
+                        value: "gh-token-value".into(),
+                        source: Source::Env(Var::GHToken),
+                        user: None,
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_for_host_returns_error_for_malformed_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hosts.yml"), "my.ghes.com: [oops").unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_ENTERPRISE_TOKEN", None),
+                ("GITHUB_ENTERPRISE_TOKEN", None),
+            ],
+            || {
+                assert!(matches!(
+                    token_for_host("my.ghes.com"),
+                    Err(TokenError::Config(TokenFromConfigError::FailToParse(_)))
+                ))
+            },
+        );
+    }
+
+    #[test]
+    fn default_host_uses_gh_host_variable() {
+        temp_env::with_var("GH_HOST", Some("https://My.GHES.com/"), || {
             assert_eq!(
-                token_from_keyring("github.com"),
-                Ok(Some(KeyringToken {
-                    value: "gh-token-value".to_owned(),
-                })),
+                default_host(),
+                Ok(DefaultHost {
+                    host: "my.ghes.com".parse().unwrap(),
+                    source: HostSource::Env
+                })
             )
         });
     }
+
+    #[test]
+    fn default_host_uses_only_host_in_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(&path, "my.ghes.com:\n    oauth_token: config-token-value\n").unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_HOST", None),
+            ],
+            || {
+                assert_eq!(
+                    default_host(),
+                    Ok(DefaultHost {
+                        host: "my.ghes.com".parse().unwrap(),
+                        source: HostSource::Config(path.to_string_lossy().into_owned())
+                    })
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn default_host_falls_back_to_github_com_with_many_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("hosts.yml"),
+            "github.com:\n    oauth_token: a\nmy.ghes.com:\n    oauth_token: b\n",
+        )
+        .unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_HOST", None),
+            ],
+            || {
+                assert_eq!(
+                    default_host(),
+                    Ok(DefaultHost {
+                        host: "github.com".parse().unwrap(),
+                        source: HostSource::Default
+                    })
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn known_hosts_merges_env_and_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(&path, "github.com:\n    oauth_token: config-token-value\n").unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_HOST", Some("my.ghes.com".as_ref())),
+                (
+                    "GH_ENTERPRISE_TOKEN",
+                    Some("gh-enterprise-token-value".as_ref()),
+                ),
+                ("GITHUB_TOKEN", None),
+                ("GH_TOKEN", None),
+            ],
+            || {
+                assert_eq!(
+                    known_hosts(),
+                    Ok(vec![
+                        KnownHost {
+                            host: "github.com".parse().unwrap(),
+                            source: Source::Config(path.to_string_lossy().into_owned())
+                        },
+                        KnownHost {
+                            host: "my.ghes.com".parse().unwrap(),
+                            source: Source::Env(Var::GHEnterpriseToken)
+                        },
+                    ])
+                )
+            },
+        );
+    }
+
+    const MULTI_ACCOUNT_HOSTS_YML: &str = "github.com:
+    users:
+        monalisa:
+            oauth_token: monalisa-token-value
+        hubot:
+            oauth_token: hubot-token-value
+    oauth_token: monalisa-token-value
+    user: monalisa
+";
+
+    #[test]
+    fn token_for_host_reports_active_user_from_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(&path, MULTI_ACCOUNT_HOSTS_YML).unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_TOKEN", None),
+                ("GITHUB_TOKEN", None),
+            ],
+            || {
+                assert_eq!(
+                    token_for_host("github.com"),
+                    Ok(Some(Token {
+                        value: "monalisa-token-value".into(),
+                        source: Source::Config(path.to_string_lossy().into_owned()),
+                        user: Some("monalisa".to_owned()),
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_for_host_and_user_ignores_env_and_active_user() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(&path, MULTI_ACCOUNT_HOSTS_YML).unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_TOKEN", Some("gh-token-value".as_ref())),
+            ],
+            || {
+                assert_eq!(
+                    token_for_host_and_user("github.com", "hubot"),
+                    Ok(Some(Token {
+                        value: "hubot-token-value".into(),
+                        source: Source::Config(path.to_string_lossy().into_owned()),
+                        user: Some("hubot".to_owned()),
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn accounts_for_host_lists_users_from_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hosts.yml"), MULTI_ACCOUNT_HOSTS_YML).unwrap();
+
+        temp_env::with_var("GH_CONFIG_DIR", Some(dir.path()), || {
+            assert_eq!(
+                accounts_for_host("github.com"),
+                Ok(vec![
+                    Account {
+                        user: "hubot".to_owned(),
+                        active: false
+                    },
+                    Account {
+                        user: "monalisa".to_owned(),
+                        active: true
+                    },
+                ])
+            );
+            assert_eq!(accounts_for_host("my.ghes.com"), Ok(vec![]));
+        });
+    }
+
+    #[test]
+    fn token_for_host_runs_gh_from_gh_path_variable() {
+        let dir = tempfile::tempdir().unwrap();
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_PATH", Some(dir.path().join("missing-gh").as_os_str())),
+                ("GH_ENTERPRISE_TOKEN", None),
+                ("GITHUB_ENTERPRISE_TOKEN", None),
+            ],
+            || {
+                assert_eq!(
+                    token_for_host("my.ghes.com"),
+                    Err(TokenError::Keyring(TokenFromKeyringError::FailToExecute(
+                        std::io::ErrorKind::NotFound
+                    )))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_resolver_gh_path_overrides_gh_path_variable() {
+        let dir = tempfile::tempdir().unwrap();
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_PATH", Some("/from/gh_path/gh".as_ref())),
+                ("GH_ENTERPRISE_TOKEN", None),
+                ("GITHUB_ENTERPRISE_TOKEN", None),
+            ],
+            || {
+                assert_eq!(
+                    TokenResolver::new()
+                        .gh_path("/custom/gh")
+                        .native_keyring(false)
+                        .runner(|program: &OsStr, _: &[&str]| {
+                            assert_eq!(program, "/custom/gh");
+                            Ok(CommandOutput {
+                                success: true,
+                                stdout: b"custom-gh-token-value\n".to_vec(),
+                                stderr: Vec::new(),
+                            })
+                        })
+                        .token_for_host("my.ghes.com"),
+                    Ok(Some(Token {
+                        value: "custom-gh-token-value".into(),
+                        source: Source::Keyring,
+                        user: None,
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_resolver_skips_sources_that_are_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("hosts.yml"),
+            "github.com:\n    oauth_token: config-token-value\n",
+        )
+        .unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GITHUB_TOKEN", Some("github-token-value".as_ref())),
+            ],
+            || {
+                assert_eq!(
+                    TokenResolver::new()
+                        .sources([SourceKind::Keyring])
+                        .native_keyring(false)
+                        .runner(gh_prints(b"keyring-token-value\n"))
+                        .token_for_host("github.com"),
+                    Ok(Some(Token {
+                        value: "keyring-token-value".into(),
+                        source: Source::Keyring,
+                        user: None,
+                    }))
+                )
+            },
+        );
+    }
+
+    #[test]
+    fn token_resolver_without_config_ignores_broken_hosts_yml() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("hosts.yml"), "github.com: [").unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_HOST", None),
+            ],
+            || {
+                let resolver = TokenResolver::new()
+                    .sources([SourceKind::Keyring])
+                    .native_keyring(false)
+                    .runner(gh_prints(b"keyring-token-value\n"));
+
+                assert_eq!(
+                    resolver.token_for_host("github.com"),
+                    Ok(Some(Token {
+                        value: "keyring-token-value".into(),
+                        source: Source::Keyring,
+                        user: None,
+                    }))
+                );
+                assert_eq!(resolver.known_hosts(), Ok(Vec::new()));
+            },
+        );
+    }
+
+    #[test]
+    fn token_resolver_uses_sources_in_the_given_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.yml");
+        std::fs::write(&path, "github.com:\n    oauth_token: config-token-value\n").unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.path().as_os_str())),
+                ("GH_TOKEN", Some("gh-token-value".as_ref())),
+            ],
+            || {
+                assert_eq!(
+                    TokenResolver::new()
+                        .sources([SourceKind::Config, SourceKind::Env])
+                        .token_for_host("github.com"),
+                    Ok(Some(Token {
+                        value: "config-token-value".into(),
+                        source: Source::Config(path.to_string_lossy().into_owned()),
+                        user: None,
+                    }))
+                );
+                assert_eq!(
+                    TokenResolver::new()
+                        .sources([])
+                        .token_for_host("github.com"),
+                    Ok(None)
+                );
+            },
+        );
+    }
+
+    fn gh_prints(stdout: &'static [u8]) -> impl CommandRunner {
+        move |_: &OsStr, _: &[&str]| {
+            Ok(CommandOutput {
+                success: true,
+                stdout: stdout.to_vec(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    fn gh_fails_with(stderr: &'static [u8]) -> impl CommandRunner {
+        move |_: &OsStr, _: &[&str]| {
+            Ok(CommandOutput {
+                success: false,
+                stdout: Vec::new(),
+                stderr: stderr.to_vec(),
+            })
+        }
+    }
+
+    #[test]
+    fn command_output_debug_leaves_out_stdout() {
+        let output = CommandOutput {
+            success: true,
+            stdout: b"keyring-token-value\n".to_vec(),
+            stderr: Vec::new(),
+        };
+
+        assert!(!format!("{output:?}").contains("keyring-token-value"));
+    }
+
+    #[test]
+    fn token_for_keyring_asks_for_token_from_gh() {
+        let runner = |program: &OsStr, args: &[&str]| {
+            assert_eq!(program, "gh");
+            assert_eq!(
+                args,
+                [
+                    "auth",
+                    "token",
+                    "--secure-storage",
+                    "--hostname",
+                    "github.com"
+                ]
+            );
+            Ok(CommandOutput {
+                success: true,
+                stdout: b"keyring-token-value\n".to_vec(),
+                stderr: Vec::new(),
+            })
+        };
+
+        assert_eq!(
+            token_from_keyring(&runner, OsStr::new("gh"), &Host::github(), None),
+            Ok(Some(KeyringToken {
+                value: "keyring-token-value".into(),
+                user: None,
+            })),
+        )
+    }
+
+    #[test]
+    fn token_for_keyring_asks_for_token_for_user() {
+        let runner = |_: &OsStr, args: &[&str]| {
+            assert_eq!(
+                args,
+                [
+                    "auth",
+                    "token",
+                    "--secure-storage",
+                    "--hostname",
+                    "github.com",
+                    "--user",
+                    "hubot"
+                ]
+            );
+            Ok(CommandOutput {
+                success: true,
+                stdout: b"hubot-token-value\n".to_vec(),
+                stderr: Vec::new(),
+            })
+        };
+
+        assert_eq!(
+            token_from_keyring(&runner, OsStr::new("gh"), &Host::github(), Some("hubot")),
+            Ok(Some(KeyringToken {
+                value: "hubot-token-value".into(),
+                user: Some("hubot".to_owned()),
+            })),
+        )
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn token_for_host_async_asks_gh_for_keyring_token() {
+        assert_eq!(
+            TokenResolver::new()
+                .sources([SourceKind::Keyring])
+                .native_keyring(false)
+                .runner(gh_prints(b"keyring-token-value\n"))
+                .token_for_host_async("github.com")
+                .await,
+            Ok(Some(Token {
+                value: "keyring-token-value".into(),
+                source: Source::Keyring,
+                user: None,
+            }))
+        );
+    }
+
+    #[test]
+    fn token_for_keyring_returns_none_when_gh_has_no_token() {
+        assert_eq!(
+            token_from_keyring(
+                &gh_fails_with(b"no oauth token found for github.com\n"),
+                OsStr::new("gh"),
+                &Host::github(),
+                None
+            ),
+            Ok(None),
+        )
+    }
+
+    #[test]
+    fn token_for_keyring_returns_error_when_gh_fails() {
+        assert_eq!(
+            token_from_keyring(
+                &gh_fails_with(b"something went wrong\n"),
+                OsStr::new("gh"),
+                &Host::github(),
+                None
+            ),
+            Err(TokenFromKeyringError::OutputStatusFail(
+                "something went wrong\n".to_owned()
+            )),
+        )
+    }
+
+    #[test]
+    fn token_for_keyring_returns_error_for_non_utf8_output() {
+        assert!(matches!(
+            token_from_keyring(
+                &gh_prints(b"\xff\xfe"),
+                OsStr::new("gh"),
+                &Host::github(),
+                None
+            ),
+            Err(TokenFromKeyringError::StdoutNotUTF8(_)),
+        ));
+        assert!(matches!(
+            token_from_keyring(
+                &gh_fails_with(b"\xff\xfe"),
+                OsStr::new("gh"),
+                &Host::github(),
+                None
+            ),
+            Err(TokenFromKeyringError::StdErrorNotUTF8(_)),
+        ));
+    }
+
+    #[test]
+    fn token_for_keyring_returns_error_when_gh_cannot_be_executed() {
+        let runner = |_: &OsStr, _: &[&str]| -> std::io::Result<CommandOutput> {
+            Err(std::io::ErrorKind::NotFound.into())
+        };
+
+        assert_eq!(
+            token_from_keyring(&runner, OsStr::new("gh"), &Host::github(), None),
+            Err(TokenFromKeyringError::FailToExecute(
+                std::io::ErrorKind::NotFound
+            )),
+        )
+    }
 }
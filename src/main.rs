@@ -1,28 +1,24 @@
-use reqwest::blocking::Client;
+use ghet_rektstension::{default_host, ClientError, ClientOptions, RestClient};
 
-fn main() -> Result<(), String> {
-    let Some(token) = ghet_rektstension::token_for_host("github.com") else {
-        return Err("oops".to_string());
-    };
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let host = default_host()?.host;
 
-    // Make an API request to /user
-    //
+    let client = RestClient::with_options(
+        ClientOptions::new(host.as_str()).extension_name("ghet-rektstension"),
+    )?;
 
-    let client = Client::new();
-    let req = client
-        .get("https://api.github.com/user")
-        .header("User-Agent", "ghet-rektstension")
-        .header("Authorization", format!("token {}", token.value));
+    // Make an API request to /user and print the resulting JSON to stdout
+    let user: serde_json::Value = client.get("user").inspect_err(|err| {
+        let suggestion = match err {
+            ClientError::Http(err) => err.scopes_suggestion(),
+            _ => None,
+        };
+        if let Some(suggestion) = suggestion {
+            eprintln!("{suggestion}");
+        }
+    })?;
 
-    let resp = client
-        .execute(req.build().map_err(|err| "Error".to_string())?)
-        .map_err(|err| "Error".to_string())?
-        .text()
-        .map_err(|err| "Error".to_string())?;
-
-    println!("Ok: {resp}");
-
-    // Print the resulting JSON to stdout
+    println!("Ok: {user}");
 
     Ok(())
 }
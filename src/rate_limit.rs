@@ -0,0 +1,228 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::{header::HeaderMap, StatusCode};
+
+// What the X-RateLimit-* and Retry-After headers of a response said.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct RateLimit {
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+    pub used: Option<u64>,
+    pub reset: Option<u64>,       // seconds since the Unix epoch
+    pub resource: Option<String>, // e.g. "core", "graphql" or "search"
+    pub retry_after: Option<u64>, // seconds, only sent with secondary rate limits
+}
+
+impl RateLimit {
+    // None when the response carries no rate limit headers at all.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+        };
+        let number = |name: &str| header(name).and_then(|value| value.parse().ok());
+
+        let rate_limit = Self {
+            limit: number("X-RateLimit-Limit"),
+            remaining: number("X-RateLimit-Remaining"),
+            used: number("X-RateLimit-Used"),
+            reset: number("X-RateLimit-Reset"),
+            resource: header("X-RateLimit-Resource").map(str::to_owned),
+            retry_after: number("Retry-After"),
+        };
+
+        (rate_limit != Self::default()).then_some(rate_limit)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    // How long until the limit resets, zero if it already has.
+    pub fn reset_in(&self) -> Option<Duration> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        self.reset
+            .map(|reset| Duration::from_secs(reset.saturating_sub(now)))
+    }
+}
+
+// Opt-in retrying of rate limited requests, set with ClientOptions::rate_limit_policy.
+// Without it, rate limited requests fail straight away.
+//
+// ClientOptions::new("github.com").rate_limit_policy(RateLimitPolicy::new().max_retries(5))
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitPolicy {
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_wait: Duration,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimitPolicy {
+    // GitHub asks to wait at least a minute before retrying after a secondary rate
+    // limit that doesn't say how long to wait.
+    pub fn new() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_secs(60),
+            max_wait: Duration::from_secs(15 * 60),
+        }
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    // Doubled on every retry when the response doesn't say how long to wait.
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    // Requests that would need a longer wait than this fail instead.
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    // How long to wait before retrying a failed request, or None if it shouldn't be
+    // retried. attempt counts the retries made so far.
+    pub(crate) fn wait(
+        &self,
+        status: StatusCode,
+        rate_limit: Option<&RateLimit>,
+        body: &str,
+        attempt: u32,
+    ) -> Option<Duration> {
+        if attempt >= self.max_retries || !is_rate_limited(status, rate_limit, body) {
+            return None;
+        }
+
+        let wait = match rate_limit {
+            Some(RateLimit {
+                retry_after: Some(retry_after),
+                ..
+            }) => Duration::from_secs(*retry_after),
+            Some(rate_limit) if rate_limit.is_exhausted() => rate_limit.reset_in()?,
+            _ => self
+                .initial_backoff
+                .saturating_mul(2u32.saturating_pow(attempt)),
+        };
+
+        (wait <= self.max_wait).then_some(wait)
+    }
+}
+
+// 403 also means missing permissions, so it only counts when the response says so.
+fn is_rate_limited(status: StatusCode, rate_limit: Option<&RateLimit>, body: &str) -> bool {
+    match status {
+        StatusCode::TOO_MANY_REQUESTS => true,
+        StatusCode::FORBIDDEN => {
+            rate_limit.is_some_and(|rate_limit| {
+                rate_limit.is_exhausted() || rate_limit.retry_after.is_some()
+            }) || body.contains("rate limit")
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn from_headers_parses_rate_limit_headers() {
+        assert_eq!(
+            RateLimit::from_headers(&headers(&[
+                ("x-ratelimit-limit", "5000"),
+                ("x-ratelimit-remaining", "4999"),
+                ("x-ratelimit-used", "1"),
+                ("x-ratelimit-reset", "1700000000"),
+                ("x-ratelimit-resource", "core"),
+            ])),
+            Some(RateLimit {
+                limit: Some(5000),
+                remaining: Some(4999),
+                used: Some(1),
+                reset: Some(1700000000),
+                resource: Some("core".to_owned()),
+                retry_after: None,
+            })
+        );
+        assert_eq!(RateLimit::from_headers(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn wait_prefers_retry_after() {
+        let rate_limit = RateLimit {
+            retry_after: Some(30),
+            ..RateLimit::default()
+        };
+
+        assert_eq!(
+            RateLimitPolicy::new().wait(StatusCode::FORBIDDEN, Some(&rate_limit), "", 0),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn wait_backs_off_exponentially_without_headers() {
+        let policy = RateLimitPolicy::new();
+        let body = "You have exceeded a secondary rate limit.";
+
+        assert_eq!(
+            policy.wait(StatusCode::FORBIDDEN, None, body, 0),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            policy.wait(StatusCode::FORBIDDEN, None, body, 2),
+            Some(Duration::from_secs(240))
+        );
+        assert_eq!(policy.wait(StatusCode::FORBIDDEN, None, body, 3), None);
+    }
+
+    #[test]
+    fn wait_ignores_other_failures() {
+        let policy = RateLimitPolicy::new();
+
+        assert_eq!(
+            policy.wait(StatusCode::FORBIDDEN, None, "Resource not accessible", 0),
+            None
+        );
+        assert_eq!(policy.wait(StatusCode::NOT_FOUND, None, "", 0), None);
+    }
+
+    #[test]
+    fn wait_gives_up_past_max_wait() {
+        let rate_limit = RateLimit {
+            retry_after: Some(3600),
+            ..RateLimit::default()
+        };
+
+        assert_eq!(
+            RateLimitPolicy::new().wait(StatusCode::TOO_MANY_REQUESTS, Some(&rate_limit), "", 0),
+            None
+        );
+    }
+}
@@ -0,0 +1,591 @@
+use std::{cmp::Reverse, ffi::OsStr};
+
+use reqwest::Url;
+
+use crate::{
+    config, CommandRunner, DefaultHostError, Host, ParseHostError, Token, TokenError, TokenResolver,
+};
+
+// A repository on a specific host, e.g. github.com/cli/cli.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Repository {
+    pub host: Host,
+    pub owner: String,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseRepositoryError {
+    InvalidUrl(String),
+    InvalidHost(ParseHostError),
+    InvalidPath(String),
+    InvalidFullName(String),
+    DefaultHost(DefaultHostError),
+}
+
+impl std::fmt::Display for ParseRepositoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidUrl(url) => write!(f, "invalid URL: {url}"),
+            Self::InvalidHost(err) => err.fmt(f),
+            Self::InvalidPath(path) => write!(f, "invalid path: {path}"),
+            Self::InvalidFullName(full_name) => {
+                write!(
+                    f,
+                    "expected the \"[HOST/]OWNER/REPO\" format, got {full_name:?}"
+                )
+            }
+            Self::DefaultHost(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseRepositoryError {}
+
+impl From<ParseHostError> for ParseRepositoryError {
+    fn from(err: ParseHostError) -> Self {
+        Self::InvalidHost(err)
+    }
+}
+
+impl From<DefaultHostError> for ParseRepositoryError {
+    fn from(err: DefaultHostError) -> Self {
+        Self::DefaultHost(err)
+    }
+}
+
+impl Repository {
+    fn new(host: Host, owner: &str, name: &str) -> Self {
+        Self {
+            host,
+            owner: owner.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    // OWNER/REPO, without the host.
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.owner, self.name)
+    }
+
+    // The token for the repository's host, as token_for_host would find it.
+    pub fn token(&self) -> Result<Option<Token>, TokenError> {
+        crate::token_for_host(self.host.as_str())
+    }
+
+    // Mirrors go-gh's repository.FromURL on top of git.ParseURL, so that scp-like
+    // remotes, e.g. git@github.com:cli/cli.git, are understood too.
+    fn from_url(url: &str) -> Result<Self, ParseRepositoryError> {
+        let url = parse_url(url)?;
+        let host = url.host_str().unwrap_or_default().parse()?;
+
+        match url
+            .path()
+            .trim_matches('/')
+            .splitn(3, '/')
+            .collect::<Vec<_>>()[..]
+        {
+            [owner, name] if !owner.is_empty() && !name.is_empty() => Ok(Self::new(
+                host,
+                owner,
+                name.strip_suffix(".git").unwrap_or(name),
+            )),
+            _ => Err(ParseRepositoryError::InvalidPath(url.path().to_owned())),
+        }
+    }
+
+    // Mirrors go-gh's repository.Parse for [HOST/]OWNER/REPO, with the host defaulting
+    // to default_host.
+    fn from_full_name(full_name: &str) -> Result<Self, ParseRepositoryError> {
+        let parts = full_name.splitn(4, '/').collect::<Vec<_>>();
+        let invalid = || ParseRepositoryError::InvalidFullName(full_name.to_owned());
+
+        if parts.iter().any(|part| part.is_empty()) {
+            return Err(invalid());
+        }
+
+        match parts[..] {
+            [host, owner, name] => Ok(Self::new(host.parse()?, owner, name)),
+            [owner, name] => Ok(Self::new(crate::default_host()?.host, owner, name)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// Mirrors go-gh's repository.Parse, accepting what gh accepts for --repo: OWNER/REPO,
+// HOST/OWNER/REPO, or a URL such as https://github.com/cli/cli.git or
+// git@github.com:cli/cli.git.
+impl std::str::FromStr for Repository {
+    type Err = ParseRepositoryError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if is_url(input) {
+            Self::from_url(input)
+        } else {
+            Self::from_full_name(input)
+        }
+    }
+}
+
+// HOST/OWNER/REPO, so that it parses back to the same repository.
+impl std::fmt::Display for Repository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.host, self.owner, self.name)
+    }
+}
+
+// Mirrors go-gh's git.isSupportedProtocol.
+const PROTOCOLS: [&str; 6] = ["ssh:", "git+ssh:", "git:", "http:", "git+https:", "https:"];
+
+fn has_protocol(url: &str) -> bool {
+    PROTOCOLS.iter().any(|protocol| url.starts_with(protocol))
+}
+
+// Mirrors go-gh's git.IsURL. Unlike parse_url, only git@ marks scp-like syntax, so
+// anything else without a scheme is read as [HOST/]OWNER/REPO.
+fn is_url(input: &str) -> bool {
+    input.starts_with("git@") || has_protocol(input)
+}
+
+// Mirrors go-gh's git.ParseURL, which treats anything with a colon but no scheme it
+// knows as scp-like ssh.
+fn parse_url(url: &str) -> Result<Url, ParseRepositoryError> {
+    let url = if !(has_protocol(url) || url.starts_with("file:"))
+        && url.contains(':')
+        && !url.contains('\\')
+    {
+        format!("ssh://{}", url.replacen(':', "/", 1))
+    } else {
+        url.to_owned()
+    };
+
+    Url::parse(&url).map_err(|_| ParseRepositoryError::InvalidUrl(url))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CurrentRepositoryError {
+    InvalidGhRepo(ParseRepositoryError),
+    FailToExecuteGit(std::io::ErrorKind),
+    GitFailed(String), // stderr
+    KnownHosts(TokenError),
+    NoRemotes,
+    NoKnownHostRemotes,
+}
+
+impl std::fmt::Display for CurrentRepositoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidGhRepo(err) => write!(f, "invalid GH_REPO: {err}"),
+            Self::FailToExecuteGit(kind) => write!(f, "failed to execute git: {kind}"),
+            Self::GitFailed(stderr) => write!(f, "git remote failed: {stderr}"),
+            Self::KnownHosts(err) => err.fmt(f),
+            Self::NoRemotes => write!(
+                f,
+                "unable to determine current repository, no git remotes configured for this repository"
+            ),
+            Self::NoKnownHostRemotes => write!(
+                f,
+                "unable to determine current repository, none of the git remotes configured for this repository point to a known GitHub host"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CurrentRepositoryError {}
+
+// Mirrors go-gh's repository.Current: GH_REPO wins, and otherwise the first git remote
+// on a host gh knows about. Remotes are tried in the order gh resolves them, starting
+// with the one `gh repo set-default` chose, then upstream, github and origin.
+pub fn current_repository() -> Result<Repository, CurrentRepositoryError> {
+    TokenResolver::new().current_repository()
+}
+
+pub(crate) fn current_repository_with(
+    resolver: &TokenResolver,
+) -> Result<Repository, CurrentRepositoryError> {
+    if let Some(gh_repo) = config::non_empty_var("GH_REPO") {
+        return gh_repo
+            .parse()
+            .map_err(CurrentRepositoryError::InvalidGhRepo);
+    }
+
+    let remotes = remotes(resolver.runner.as_ref())?;
+    if remotes.is_empty() {
+        return Err(CurrentRepositoryError::NoRemotes);
+    }
+
+    let known_hosts = resolver
+        .configured_hosts()
+        .map_err(CurrentRepositoryError::KnownHosts)?;
+
+    remotes
+        .into_iter()
+        .find(|remote| known_hosts.contains(&remote.repository.host))
+        .map(Remote::into_repository)
+        .ok_or(CurrentRepositoryError::NoKnownHostRemotes)
+}
+
+struct Remote {
+    name: String,
+    repository: Repository,
+    resolved: Option<String>, // "base", or OWNER/REPO on the remote's host
+}
+
+impl Remote {
+    fn score(&self) -> u8 {
+        if self.resolved.is_some() {
+            return 4;
+        }
+
+        match self.name.to_ascii_lowercase().as_str() {
+            "upstream" => 3,
+            "github" => 2,
+            "origin" => 1,
+            _ => 0,
+        }
+    }
+
+    fn into_repository(self) -> Repository {
+        match self
+            .resolved
+            .as_deref()
+            .filter(|resolved| *resolved != "base")
+            .and_then(|resolved| resolved.split_once('/'))
+        {
+            Some((owner, name)) => Repository::new(self.repository.host, owner, name),
+            None => self.repository,
+        }
+    }
+}
+
+// Remotes whose fetch URL points at a repository, best first. Remotes that don't,
+// e.g. local paths, are left out.
+fn remotes(runner: &dyn CommandRunner) -> Result<Vec<Remote>, CurrentRepositoryError> {
+    let remotes = git(runner, &["remote", "-v"])?;
+    // git config exits with 1 when nothing matches, i.e. no remote has been resolved.
+    let resolved = git(
+        runner,
+        &["config", "--get-regexp", r"^remote\..*\.gh-resolved$"],
+    )
+    .unwrap_or_default();
+
+    let mut remotes = remotes
+        .lines()
+        .filter_map(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [name, url, "(fetch)"] => Some(Remote {
+                    name: name.to_owned(),
+                    repository: Repository::from_url(url).ok()?,
+                    resolved: resolved.lines().find_map(|line| {
+                        let (key, value) = line.split_once(' ')?;
+                        (key.strip_prefix("remote.")?.strip_suffix(".gh-resolved")? == name)
+                            .then(|| value.trim().to_owned())
+                    }),
+                }),
+                _ => None,
+            },
+        )
+        .collect::<Vec<_>>();
+
+    // Stable, so remotes that score the same stay in git's order.
+    remotes.sort_by_key(|remote| Reverse(remote.score()));
+
+    Ok(remotes)
+}
+
+fn git(runner: &dyn CommandRunner, args: &[&str]) -> Result<String, CurrentRepositoryError> {
+    let output = runner
+        .run(OsStr::new("git"), args)
+        .map_err(|err| CurrentRepositoryError::FailToExecuteGit(err.kind()))?;
+
+    if output.success {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(CurrentRepositoryError::GitFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::CommandOutput;
+
+    fn repository(host: &str, owner: &str, name: &str) -> Repository {
+        Repository::new(host.parse().unwrap(), owner, name)
+    }
+
+    // Scripts `git remote -v` and `git config --get-regexp`, the latter failing like
+    // git does when there are no matches.
+    fn git_with(remotes: &'static str, resolved: &'static str) -> impl CommandRunner {
+        move |program: &OsStr, args: &[&str]| {
+            assert_eq!(program, "git");
+            let stdout = match args[0] {
+                "remote" => remotes,
+                _ => resolved,
+            };
+            Ok(CommandOutput {
+                success: !stdout.is_empty() || args[0] == "remote",
+                stdout: stdout.as_bytes().to_vec(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+
+    fn current_repository_with(
+        runner: impl CommandRunner + 'static,
+    ) -> Result<Repository, CurrentRepositoryError> {
+        TokenResolver::new().runner(runner).current_repository()
+    }
+
+    fn with_known_hosts<R>(dir: &Path, gh_repo: Option<&str>, closure: impl FnOnce() -> R) -> R {
+        std::fs::write(
+            dir.join("hosts.yml"),
+            "github.com:\n    user: monalisa\nmy.ghes.com:\n    user: monalisa\n",
+        )
+        .unwrap();
+
+        temp_env::with_vars(
+            [
+                ("GH_CONFIG_DIR", Some(dir.to_str().unwrap())),
+                ("GH_REPO", gh_repo),
+                ("GH_HOST", None),
+                ("GH_TOKEN", None),
+                ("GITHUB_TOKEN", None),
+            ],
+            closure,
+        )
+    }
+
+    #[test]
+    fn from_url_parses_remote_urls() {
+        let cli = repository("github.com", "cli", "cli");
+
+        assert_eq!(
+            Repository::from_url("https://github.com/cli/cli.git"),
+            Ok(cli.clone())
+        );
+        assert_eq!(
+            Repository::from_url("git@github.com:cli/cli.git"),
+            Ok(cli.clone())
+        );
+        assert_eq!(
+            Repository::from_url("ssh://git@ssh.github.com:443/cli/cli"),
+            Ok(cli.clone())
+        );
+        assert_eq!(
+            Repository::from_url("git+ssh://git@github.com/cli/cli.git"),
+            Ok(cli.clone())
+        );
+        assert_eq!(Repository::from_url("git://github.com/cli/cli"), Ok(cli));
+        assert_eq!(
+            Repository::from_url("https://My.GHES.com/octo-org/octo-repo/"),
+            Ok(repository("my.ghes.com", "octo-org", "octo-repo"))
+        );
+    }
+
+    #[test]
+    fn from_url_rejects_urls_that_are_not_repositories() {
+        assert_eq!(
+            Repository::from_url("https://github.com/cli"),
+            Err(ParseRepositoryError::InvalidPath("/cli".to_owned()))
+        );
+        assert_eq!(
+            Repository::from_url("https://github.com/cli/cli/pulls"),
+            Err(ParseRepositoryError::InvalidPath(
+                "/cli/cli/pulls".to_owned()
+            ))
+        );
+        assert_eq!(
+            Repository::from_url("/home/monalisa/cli"),
+            Err(ParseRepositoryError::InvalidUrl(
+                "/home/monalisa/cli".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn from_full_name_defaults_host() {
+        temp_env::with_var("GH_HOST", Some("my.ghes.com"), || {
+            assert_eq!(
+                Repository::from_full_name("cli/cli"),
+                Ok(repository("my.ghes.com", "cli", "cli"))
+            );
+            assert_eq!(
+                Repository::from_full_name("github.com/cli/cli"),
+                Ok(repository("github.com", "cli", "cli"))
+            );
+            assert_eq!(
+                Repository::from_full_name("cli//cli"),
+                Err(ParseRepositoryError::InvalidFullName("cli//cli".to_owned()))
+            );
+            assert_eq!(
+                Repository::from_full_name("cli"),
+                Err(ParseRepositoryError::InvalidFullName("cli".to_owned()))
+            );
+        });
+    }
+
+    #[test]
+    fn from_str_accepts_what_gh_accepts() {
+        temp_env::with_var("GH_HOST", Some("my.ghes.com"), || {
+            let parse = |input: &str| input.parse::<Repository>();
+
+            assert_eq!(
+                parse("cli/cli"),
+                Ok(repository("my.ghes.com", "cli", "cli"))
+            );
+            assert_eq!(
+                parse("GitHub.com/cli/cli"),
+                Ok(repository("github.com", "cli", "cli"))
+            );
+            assert_eq!(
+                parse("https://github.com/cli/cli.git"),
+                Ok(repository("github.com", "cli", "cli"))
+            );
+            assert_eq!(
+                parse("git@tenant.ghe.com:cli/cli"),
+                Ok(repository("tenant.ghe.com", "cli", "cli"))
+            );
+            assert_eq!(
+                parse("github.com/cli/cli/pulls"),
+                Err(ParseRepositoryError::InvalidFullName(
+                    "github.com/cli/cli/pulls".to_owned()
+                ))
+            );
+            assert_eq!(
+                parse("https://github.com/cli"),
+                Err(ParseRepositoryError::InvalidPath("/cli".to_owned()))
+            );
+        });
+    }
+
+    #[test]
+    fn display_parses_back_to_the_same_repository() {
+        let repository = repository("my.ghes.com", "cli", "cli");
+
+        assert_eq!(repository.to_string(), "my.ghes.com/cli/cli");
+        assert_eq!(repository.to_string().parse(), Ok(repository));
+    }
+
+    #[test]
+    fn current_repository_prefers_gh_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = |_: &OsStr, _: &[&str]| -> std::io::Result<CommandOutput> {
+            panic!("git shouldn't be run when GH_REPO is set")
+        };
+
+        with_known_hosts(dir.path(), Some("my.ghes.com/cli/cli"), || {
+            assert_eq!(
+                current_repository_with(runner),
+                Ok(repository("my.ghes.com", "cli", "cli"))
+            );
+        });
+    }
+
+    #[test]
+    fn current_repository_prefers_upstream_over_origin() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = git_with(
+            "origin\tgit@github.com:monalisa/cli.git (fetch)\n\
+             origin\tgit@github.com:monalisa/cli.git (push)\n\
+             upstream\thttps://github.com/cli/cli.git (fetch)\n\
+             upstream\thttps://github.com/cli/cli.git (push)\n",
+            "",
+        );
+
+        with_known_hosts(dir.path(), None, || {
+            assert_eq!(
+                current_repository_with(runner),
+                Ok(repository("github.com", "cli", "cli"))
+            );
+        });
+    }
+
+    #[test]
+    fn current_repository_prefers_resolved_remote() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = git_with(
+            "origin\tgit@my.ghes.com:monalisa/cli.git (fetch)\n\
+             upstream\thttps://github.com/cli/cli.git (fetch)\n",
+            "remote.origin.gh-resolved octo-org/cli\n",
+        );
+
+        with_known_hosts(dir.path(), None, || {
+            assert_eq!(
+                current_repository_with(runner),
+                Ok(repository("my.ghes.com", "octo-org", "cli"))
+            );
+        });
+    }
+
+    #[test]
+    fn current_repository_skips_remotes_on_unknown_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let runner = git_with(
+            "upstream\thttps://gitlab.com/cli/cli.git (fetch)\n\
+             origin\thttps://github.com/monalisa/cli.git (fetch)\n",
+            "",
+        );
+        let unknown = git_with("origin\thttps://gitlab.com/cli/cli.git (fetch)\n", "");
+
+        with_known_hosts(dir.path(), None, || {
+            assert_eq!(
+                current_repository_with(runner),
+                Ok(repository("github.com", "monalisa", "cli"))
+            );
+            assert_eq!(
+                current_repository_with(unknown),
+                Err(CurrentRepositoryError::NoKnownHostRemotes)
+            );
+        });
+    }
+
+    #[test]
+    fn current_repository_only_knows_the_resolvers_hosts() {
+        let dir = tempfile::tempdir().unwrap();
+        let resolver = TokenResolver::new()
+            .sources([crate::SourceKind::Keyring])
+            .runner(git_with(
+                "origin\thttps://github.com/cli/cli.git (fetch)\n",
+                "",
+            ));
+
+        with_known_hosts(dir.path(), None, || {
+            assert_eq!(
+                resolver.current_repository(),
+                Err(CurrentRepositoryError::NoKnownHostRemotes)
+            );
+        });
+    }
+
+    #[test]
+    fn current_repository_fails_without_remotes() {
+        let dir = tempfile::tempdir().unwrap();
+        let not_a_repository = |_: &OsStr, _: &[&str]| {
+            Ok(CommandOutput {
+                success: false,
+                stdout: Vec::new(),
+                stderr: b"fatal: not a git repository (or any of the parent directories): .git\n"
+                    .to_vec(),
+            })
+        };
+
+        with_known_hosts(dir.path(), None, || {
+            assert_eq!(
+                current_repository_with(git_with("", "")),
+                Err(CurrentRepositoryError::NoRemotes)
+            );
+            assert_eq!(
+                current_repository_with(not_a_repository),
+                Err(CurrentRepositoryError::GitFailed(
+                    "fatal: not a git repository (or any of the parent directories): .git"
+                        .to_owned()
+                ))
+            );
+        });
+    }
+}
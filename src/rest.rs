@@ -0,0 +1,908 @@
+use std::{collections::VecDeque, marker::PhantomData, time::Duration};
+
+use reqwest::{
+    blocking::Client,
+    header::{HeaderMap, LINK},
+    Method, Url,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    client::{ClientError, ClientOptions, Response, Transport},
+    RateLimit,
+};
+
+// A client for the REST API of a single host, authenticated with the host's token.
+//
+// let client = RestClient::new("github.com")?;
+// let repo: Repo = client.get("repos/cli/cli")?;
+#[derive(Debug, Clone)]
+pub struct RestClient {
+    transport: Transport,
+    client: Client,
+    base_url: String,
+}
+
+impl RestClient {
+    pub fn new(host: &str) -> Result<Self, ClientError> {
+        Self::with_options(ClientOptions::new(host))
+    }
+
+    pub fn with_options(options: ClientOptions) -> Result<Self, ClientError> {
+        let transport = Transport::new(&options)?;
+
+        Ok(Self {
+            client: transport.client()?,
+            base_url: base_url(&transport),
+            transport,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // A copy of this client that caches responses for the given time, e.g. for a
+    // single request:
+    //
+    // client.with_cache_ttl(Duration::from_secs(3600)).get::<Repo>("repos/cli/cli")?;
+    pub fn with_cache_ttl(&self, cache_ttl: Duration) -> Self {
+        Self {
+            transport: self.transport.with_cache_ttl(cache_ttl),
+            ..self.clone()
+        }
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        self.request(Method::GET, path, None::<&()>)
+    }
+
+    pub fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ClientError> {
+        self.request(Method::POST, path, Some(body))
+    }
+
+    pub fn patch<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ClientError> {
+        self.request(Method::PATCH, path, Some(body))
+    }
+
+    pub fn put<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ClientError> {
+        self.request(Method::PUT, path, Some(body))
+    }
+
+    // Some deletes respond with what's left, e.g. an issue's remaining labels, which is
+    // ignored here. Use request to read it.
+    pub fn delete(&self, path: &str) -> Result<(), ClientError> {
+        self.send(Method::DELETE, path, None::<&()>).map(|_| ())
+    }
+
+    // Lazily walks every page of a list endpoint, yielding its items one at a time.
+    //
+    // for issue in client.paginate::<Issue>("repos/cli/cli/issues").per_page(100) {
+    //     println!("{}", issue?.title);
+    // }
+    pub fn paginate<T: DeserializeOwned>(&self, path: &str) -> Paginate<'_, T> {
+        Paginate {
+            client: self,
+            pages: Pages::new(Transport::url(&self.base_url, path)),
+            item: PhantomData,
+        }
+    }
+
+    // Paths are relative to the host's API URL, e.g. "repos/cli/cli". An empty response
+    // body, e.g. a 204, deserializes like JSON null so that () and Option<T> work.
+    pub fn request<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<T, ClientError> {
+        self.send(method, path, body).and_then(json_or_null)
+    }
+
+    pub(crate) fn send<B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<Response, ClientError> {
+        let url = Transport::url(&self.base_url, path);
+        let mut request = self.client.request(method, &url);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        self.transport.send(&self.client, request)
+    }
+
+    // The rate limit as of the latest response to this client or its clones.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.transport.rate_limit()
+    }
+}
+
+// The async counterpart of RestClient, for use with tokio.
+//
+// let client = AsyncRestClient::new("github.com").await?;
+// let repo: Repo = client.get("repos/cli/cli").await?;
+#[cfg(feature = "async")]
+#[derive(Debug, Clone)]
+pub struct AsyncRestClient {
+    transport: Transport,
+    client: reqwest::Client,
+    base_url: String,
+}
+
+#[cfg(feature = "async")]
+impl AsyncRestClient {
+    pub async fn new(host: &str) -> Result<Self, ClientError> {
+        Self::with_options(ClientOptions::new(host)).await
+    }
+
+    pub async fn with_options(options: ClientOptions) -> Result<Self, ClientError> {
+        let transport = Transport::new_async(&options).await?;
+
+        Ok(Self {
+            client: transport.async_client()?,
+            base_url: base_url(&transport),
+            transport,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn with_cache_ttl(&self, cache_ttl: Duration) -> Self {
+        Self {
+            transport: self.transport.with_cache_ttl(cache_ttl),
+            ..self.clone()
+        }
+    }
+
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, ClientError> {
+        self.request(Method::GET, path, None::<&()>).await
+    }
+
+    pub async fn post<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ClientError> {
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    pub async fn patch<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ClientError> {
+        self.request(Method::PATCH, path, Some(body)).await
+    }
+
+    pub async fn put<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<T, ClientError> {
+        self.request(Method::PUT, path, Some(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<(), ClientError> {
+        self.send(Method::DELETE, path, None::<&()>)
+            .await
+            .map(|_| ())
+    }
+
+    // while let Some(issue) = issues.next().await { .. }
+    pub fn paginate<T: DeserializeOwned>(&self, path: &str) -> AsyncPaginate<'_, T> {
+        AsyncPaginate {
+            client: self,
+            pages: Pages::new(Transport::url(&self.base_url, path)),
+            item: PhantomData,
+        }
+    }
+
+    pub async fn request<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<T, ClientError> {
+        self.send(method, path, body).await.and_then(json_or_null)
+    }
+
+    async fn send<B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> Result<Response, ClientError> {
+        let url = Transport::url(&self.base_url, path);
+        let mut request = self.client.request(method, &url);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        self.transport.send_async(&self.client, request).await
+    }
+
+    // The rate limit as of the latest response to this client or its clones.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.transport.rate_limit()
+    }
+}
+
+fn base_url(transport: &Transport) -> String {
+    transport
+        .base_url
+        .clone()
+        .unwrap_or_else(|| transport.host.rest_url())
+}
+
+fn json_or_null<T: DeserializeOwned>(mut response: Response) -> Result<T, ClientError> {
+    if response.body.is_empty() {
+        response.body = b"null".to_vec();
+    }
+
+    response.json()
+}
+
+// Iterator returned by RestClient::paginate. A failed request is yielded as an error
+// and ends the iteration.
+#[derive(Debug)]
+pub struct Paginate<'a, T> {
+    client: &'a RestClient,
+    pages: Pages,
+    item: PhantomData<T>,
+}
+
+impl<T> Paginate<'_, T> {
+    // Only applies to the first request, as later ones follow the next links which
+    // already carry it.
+    pub fn per_page(mut self, per_page: u32) -> Self {
+        self.pages.per_page = Some(per_page);
+        self
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for Paginate<'_, T> {
+    type Item = Result<T, ClientError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pages.pop() {
+                return Some(item);
+            }
+
+            let url = self.pages.next_url()?;
+            let response = self.client.send(Method::GET, &url, None::<&()>);
+            if let Err(err) = response.and_then(|response| self.pages.add(response)) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+// Returned by AsyncRestClient::paginate, like Paginate but with an async next.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct AsyncPaginate<'a, T> {
+    client: &'a AsyncRestClient,
+    pages: Pages,
+    item: PhantomData<T>,
+}
+
+#[cfg(feature = "async")]
+impl<T: DeserializeOwned> AsyncPaginate<'_, T> {
+    pub fn per_page(mut self, per_page: u32) -> Self {
+        self.pages.per_page = Some(per_page);
+        self
+    }
+
+    pub async fn next(&mut self) -> Option<Result<T, ClientError>> {
+        loop {
+            if let Some(item) = self.pages.pop() {
+                return Some(item);
+            }
+
+            let url = self.pages.next_url()?;
+            let response = self.client.send(Method::GET, &url, None::<&()>).await;
+            if let Err(err) = response.and_then(|response| self.pages.add(response)) {
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+// How far a pagination has got, whether blocking or async.
+#[derive(Debug)]
+struct Pages {
+    next_url: Option<String>,
+    per_page: Option<u32>,
+    items: VecDeque<serde_json::Value>,
+}
+
+impl Pages {
+    fn new(url: String) -> Self {
+        Self {
+            next_url: Some(url),
+            per_page: None,
+            items: VecDeque::new(),
+        }
+    }
+
+    // None once the last page has been fetched, or a request failed.
+    fn next_url(&mut self) -> Option<String> {
+        let url = self.next_url.take()?;
+        let Some(per_page) = self.per_page.take() else {
+            return Some(url);
+        };
+        // A URL that doesn't parse fails once it's requested, with a better error.
+        let Ok(mut parsed) = Url::parse(&url) else {
+            return Some(url);
+        };
+
+        // Replaces any per_page already in the path, rather than sending it twice.
+        let query: Vec<(String, String)> = parsed
+            .query_pairs()
+            .filter(|(name, _)| name != "per_page")
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        parsed
+            .query_pairs_mut()
+            .clear()
+            .extend_pairs(query)
+            .append_pair("per_page", &per_page.to_string());
+
+        Some(parsed.into())
+    }
+
+    fn add(&mut self, response: Response) -> Result<(), ClientError> {
+        self.next_url = next_link(&response.headers);
+        self.items.extend(page_items(response.json()?)?);
+
+        Ok(())
+    }
+
+    fn pop<T: DeserializeOwned>(&mut self) -> Option<Result<T, ClientError>> {
+        self.items.pop_front().map(|item| {
+            serde_json::from_value(item).map_err(|err| ClientError::Json(err.to_string()))
+        })
+    }
+}
+
+// The keys list endpoints wrap their array in, e.g. search's {"total_count":..,"items":[..]}
+// or {"total_count":..,"workflow_runs":[..]}.
+const PAGE_ITEM_KEYS: &[&str] = &[
+    "items",
+    "repositories",
+    "workflow_runs",
+    "workflows",
+    "jobs",
+    "artifacts",
+    "installations",
+    "secrets",
+    "variables",
+    "runners",
+    "runner_groups",
+    "check_runs",
+    "check_suites",
+    "environments",
+    "actions_caches",
+];
+
+// Most list endpoints return a bare array, others one under one of PAGE_ITEM_KEYS.
+fn page_items(page: serde_json::Value) -> Result<Vec<serde_json::Value>, ClientError> {
+    let items = match page {
+        serde_json::Value::Array(items) => Some(items),
+        serde_json::Value::Object(mut fields) => PAGE_ITEM_KEYS.iter().find_map(|key| match fields
+            .remove(*key)
+        {
+            Some(serde_json::Value::Array(items)) => Some(items),
+            _ => None,
+        }),
+        _ => None,
+    };
+
+    items.ok_or_else(|| ClientError::Json("page has no list of items".to_owned()))
+}
+
+// Picks the URL out of e.g. `<https://api.github.com/...&page=2>; rel="next"`.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .find_map(|link| {
+            let (url, params) = link.split_once(';')?;
+            params
+                .split(';')
+                .any(|param| param.trim() == r#"rel="next""#)
+                .then(|| {
+                    url.trim()
+                        .trim_start_matches('<')
+                        .trim_end_matches('>')
+                        .to_owned()
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::RateLimitPolicy;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Repo {
+        full_name: String,
+    }
+
+    fn client(server: &mockito::Server) -> RestClient {
+        RestClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .extension_name("gh-test")
+                .base_url(&server.url()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rest_client_uses_api_url_for_host() {
+        let rest_url = |host: &str| {
+            RestClient::with_options(ClientOptions::new(host).auth_token("ghp_xxxx"))
+                .unwrap()
+                .base_url()
+                .to_owned()
+        };
+
+        assert_eq!(rest_url("github.com"), "https://api.github.com/");
+        assert_eq!(rest_url("my.ghes.com"), "https://my.ghes.com/api/v3/");
+        assert_eq!(rest_url("tenant.ghe.com"), "https://api.tenant.ghe.com/");
+    }
+
+    #[test]
+    fn get_sends_github_headers_and_deserializes_json() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/repos/cli/cli")
+            .match_header("authorization", "token ghp_xxxx")
+            .match_header("accept", "application/vnd.github+json")
+            .match_header("x-github-api-version", "2022-11-28")
+            .match_header(
+                "user-agent",
+                mockito::Matcher::Regex("^gh-test ghet-rektstension/".to_owned()),
+            )
+            .with_body(r#"{"full_name":"cli/cli","private":false}"#)
+            .create();
+
+        assert_eq!(
+            client(&server).get::<Repo>("repos/cli/cli"),
+            Ok(Repo {
+                full_name: "cli/cli".to_owned()
+            })
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn token_is_not_sent_to_other_hosts() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/x")
+            .match_header("authorization", mockito::Matcher::Missing)
+            .with_body("{}")
+            .create();
+
+        let client =
+            RestClient::with_options(ClientOptions::new("github.com").auth_token("ghp_secret"))
+                .unwrap();
+
+        assert_eq!(
+            client.get::<serde_json::Value>(&format!("{}/x", server.url())),
+            Ok(serde_json::json!({}))
+        );
+        mock.assert();
+    }
+
+    #[test]
+    fn post_sends_json_body() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/repos/cli/cli/issues")
+            .match_body(mockito::Matcher::Json(
+                serde_json::json!({"title": "Broken"}),
+            ))
+            .with_status(201)
+            .with_body(r#"{"number":1}"#)
+            .create();
+
+        let issue: serde_json::Value = client(&server)
+            .post(
+                "repos/cli/cli/issues",
+                &serde_json::json!({"title": "Broken"}),
+            )
+            .unwrap();
+
+        assert_eq!(issue["number"], 1);
+        mock.assert();
+    }
+
+    #[test]
+    fn delete_accepts_empty_response() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("DELETE", "/repos/cli/cli/subscription")
+            .with_status(204)
+            .create();
+
+        assert_eq!(client(&server).delete("repos/cli/cli/subscription"), Ok(()));
+    }
+
+    #[test]
+    fn delete_ignores_response_body() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("DELETE", "/repos/cli/cli/issues/1/labels/bug")
+            .with_body(r#"[{"name":"enhancement"}]"#)
+            .create();
+
+        assert_eq!(
+            client(&server).delete("repos/cli/cli/issues/1/labels/bug"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn request_fails_for_error_status() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/repos/cli/missing")
+            .with_status(404)
+            .with_body(r#"{"message":"Not Found"}"#)
+            .create();
+
+        let Err(ClientError::Http(err)) = client(&server).get::<Repo>("repos/cli/missing") else {
+            panic!("expected an HTTP error");
+        };
+        assert!(err.is_not_found());
+        assert_eq!(err.message, "Not Found");
+        assert_eq!(err.url, format!("{}/repos/cli/missing", server.url()));
+    }
+
+    #[test]
+    fn paginate_follows_next_links() {
+        let mut server = mockito::Server::new();
+        let first = server
+            .mock("GET", "/repos/cli/cli/issues?per_page=2")
+            .with_header(
+                "link",
+                &format!(
+                    r#"<{0}/repositories/1/issues?per_page=2&page=2>; rel="next", <{0}/repositories/1/issues?per_page=2&page=2>; rel="last""#,
+                    server.url()
+                ),
+            )
+            .with_body(r#"[{"number":1},{"number":2}]"#)
+            .create();
+        let second = server
+            .mock("GET", "/repositories/1/issues?per_page=2&page=2")
+            .with_body(r#"[{"number":3}]"#)
+            .create();
+
+        let client = client(&server);
+        let mut issues = client
+            .paginate::<serde_json::Value>("repos/cli/cli/issues")
+            .per_page(2);
+
+        assert_eq!(issues.next().unwrap().unwrap()["number"], 1);
+        second.expect(0).assert();
+        let numbers: Vec<_> = issues
+            .map(|issue| issue.unwrap()["number"].clone())
+            .collect();
+
+        assert_eq!(numbers, [2, 3]);
+        first.assert();
+    }
+
+    #[test]
+    fn paginate_replaces_per_page_in_path() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/repos/cli/cli/issues?state=all&per_page=100")
+            .with_body("[]")
+            .create();
+
+        let client = client(&server);
+        let issues: Vec<_> = client
+            .paginate::<serde_json::Value>("repos/cli/cli/issues?per_page=30&state=all")
+            .per_page(100)
+            .collect();
+
+        assert!(issues.is_empty());
+        mock.assert();
+    }
+
+    #[test]
+    fn paginate_flattens_wrapped_pages() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/search/repositories?q=gh-extension")
+            .with_body(r#"{"total_count":2,"incomplete_results":false,"items":[{"full_name":"cli/gh-a"},{"full_name":"cli/gh-b"}]}"#)
+            .create();
+
+        let repos: Result<Vec<Repo>, _> = client(&server)
+            .paginate("search/repositories?q=gh-extension")
+            .collect();
+
+        assert_eq!(
+            repos,
+            Ok(vec![
+                Repo {
+                    full_name: "cli/gh-a".to_owned()
+                },
+                Repo {
+                    full_name: "cli/gh-b".to_owned()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn paginate_fails_for_objects_without_a_known_list() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/repos/cli/cli/teams-diff")
+            .with_body(r#"{"added":[{"slug":"a"}],"removed":[{"slug":"b"}]}"#)
+            .create();
+
+        let teams: Result<Vec<serde_json::Value>, _> = client(&server)
+            .paginate("repos/cli/cli/teams-diff")
+            .collect();
+
+        assert_eq!(
+            teams,
+            Err(ClientError::Json("page has no list of items".to_owned()))
+        );
+    }
+
+    #[test]
+    fn paginate_stops_after_an_error() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/repos/cli/missing/issues")
+            .with_status(404)
+            .with_body(r#"{"message":"Not Found"}"#)
+            .create();
+
+        let client = client(&server);
+        let mut issues = client.paginate::<serde_json::Value>("repos/cli/missing/issues");
+
+        assert!(matches!(
+            issues.next(),
+            Some(Err(ClientError::Http(err))) if err.status == 404
+        ));
+        assert!(issues.next().is_none());
+    }
+
+    #[test]
+    fn rate_limit_reflects_latest_response() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/user")
+            .with_header("x-ratelimit-limit", "5000")
+            .with_header("x-ratelimit-remaining", "4321")
+            .with_header("x-ratelimit-reset", "1700000000")
+            .with_header("x-ratelimit-resource", "core")
+            .with_body(r#"{"login":"monalisa"}"#)
+            .create();
+
+        let client = client(&server);
+        assert_eq!(client.rate_limit(), None);
+        client.get::<serde_json::Value>("user").unwrap();
+
+        let rate_limit = client.rate_limit().unwrap();
+        assert_eq!(rate_limit.remaining, Some(4321));
+        assert_eq!(rate_limit.resource.as_deref(), Some("core"));
+    }
+
+    #[test]
+    fn rate_limited_requests_fail_without_policy() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/user")
+            .with_status(429)
+            .with_header("retry-after", "0")
+            .create();
+
+        assert!(matches!(
+            client(&server).get::<serde_json::Value>("user"),
+            Err(ClientError::Http(err)) if err.status == 429
+        ));
+        mock.assert();
+    }
+
+    #[test]
+    fn rate_limited_requests_are_retried_with_policy() {
+        let mut server = mockito::Server::new();
+        let limited = server
+            .mock("GET", "/user")
+            .with_status(403)
+            .with_body(r#"{"message":"You have exceeded a secondary rate limit."}"#)
+            .expect(2)
+            .create();
+        let ok = server
+            .mock("GET", "/user")
+            .with_body(r#"{"login":"monalisa"}"#)
+            .create();
+
+        let client = RestClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .base_url(&server.url())
+                .rate_limit_policy(
+                    RateLimitPolicy::new().initial_backoff(Duration::from_millis(1)),
+                ),
+        )
+        .unwrap();
+
+        let user: serde_json::Value = client.get("user").unwrap();
+        assert_eq!(user["login"], "monalisa");
+        limited.assert();
+        ok.assert();
+    }
+
+    #[test]
+    fn with_cache_ttl_reuses_cached_responses() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/repos/cli/cli")
+            .with_body(r#"{"full_name":"cli/cli"}"#)
+            .expect(2)
+            .create();
+
+        let dir = tempfile::tempdir().unwrap();
+        let client = RestClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .base_url(&server.url())
+                .cache_dir(dir.path()),
+        )
+        .unwrap();
+        let cached = client.with_cache_ttl(Duration::from_secs(3600));
+
+        for _ in 0..2 {
+            assert_eq!(
+                cached.get::<Repo>("repos/cli/cli"),
+                Ok(Repo {
+                    full_name: "cli/cli".to_owned()
+                })
+            );
+        }
+        client.get::<Repo>("repos/cli/cli").unwrap();
+
+        mock.assert();
+    }
+
+    #[test]
+    fn stale_cached_responses_are_revalidated() {
+        let mut server = mockito::Server::new();
+        let fetched = server
+            .mock("GET", "/repos/cli/cli")
+            .match_header("if-none-match", mockito::Matcher::Missing)
+            .with_header("etag", r#""abc""#)
+            .with_body(r#"{"full_name":"cli/cli"}"#)
+            .create();
+        let not_modified = server
+            .mock("GET", "/repos/cli/cli")
+            .match_header("if-none-match", r#""abc""#)
+            .with_status(304)
+            .expect(2)
+            .create();
+
+        let dir = tempfile::tempdir().unwrap();
+        let client = RestClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .base_url(&server.url())
+                .cache_dir(dir.path())
+                .cache_ttl(Duration::ZERO),
+        )
+        .unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(
+                client.get::<Repo>("repos/cli/cli"),
+                Ok(Repo {
+                    full_name: "cli/cli".to_owned()
+                })
+            );
+        }
+
+        fetched.assert();
+        not_modified.assert();
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_get_deserializes_json() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/repos/cli/cli")
+            .match_header("authorization", "token ghp_xxxx")
+            .with_body(r#"{"full_name":"cli/cli"}"#)
+            .create_async()
+            .await;
+
+        let client = AsyncRestClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .base_url(&server.url()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            client.get::<Repo>("repos/cli/cli").await,
+            Ok(Repo {
+                full_name: "cli/cli".to_owned()
+            })
+        );
+        mock.assert_async().await;
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn async_paginate_follows_next_links() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/repos/cli/cli/issues")
+            .with_header(
+                "link",
+                &format!(
+                    r#"<{}/repositories/1/issues?page=2>; rel="next""#,
+                    server.url()
+                ),
+            )
+            .with_body(r#"[{"number":1}]"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/repositories/1/issues?page=2")
+            .with_body(r#"[{"number":2}]"#)
+            .create_async()
+            .await;
+
+        let client = AsyncRestClient::with_options(
+            ClientOptions::new("github.com")
+                .auth_token("ghp_xxxx")
+                .base_url(&server.url()),
+        )
+        .await
+        .unwrap();
+        let mut issues = client.paginate::<serde_json::Value>("repos/cli/cli/issues");
+
+        let mut numbers = Vec::new();
+        while let Some(issue) = issues.next().await {
+            numbers.push(issue.unwrap()["number"].clone());
+        }
+
+        assert_eq!(numbers, [1, 2]);
+    }
+}
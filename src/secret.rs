@@ -0,0 +1,92 @@
+use reqwest::header::{HeaderValue, InvalidHeaderValue};
+use serde::{Deserialize, Deserializer};
+use zeroize::Zeroizing;
+
+use crate::token_kind;
+
+// A token value that is wiped from memory when dropped. Debug and Display are masked,
+// so reading the secret always goes through expose.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    // Builds an Authorization header for the token, marked as sensitive so that HTTP
+    // clients leave it out of their own logging. Fails for tokens with characters that
+    // can't go in a header, e.g. a stray newline.
+    pub fn authorization_header(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        let mut header = Zeroizing::new(String::with_capacity("token ".len() + self.0.len()));
+        header.push_str("token ");
+        header.push_str(&self.0);
+
+        HeaderValue::from_str(&header).map(|mut value| {
+            value.set_sensitive(true);
+            value
+        })
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::from(value.to_owned())
+    }
+}
+
+// For tokens in gh's hosts.yml, so they're wiped along with the rest of the config.
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl std::fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&token_kind::mask(&self.0))
+    }
+}
+
+impl std::fmt::Display for SecretString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&token_kind::mask(&self.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secret_string_masks_its_value() {
+        let secret = SecretString::from("ghp_supersecret");
+
+        assert_eq!(secret.expose(), "ghp_supersecret");
+        assert_eq!(format!("{secret}"), "ghp_***");
+        assert_eq!(format!("{secret:?}"), "ghp_***");
+    }
+
+    #[test]
+    fn authorization_header_is_sensitive() {
+        let header = SecretString::from("ghp_supersecret")
+            .authorization_header()
+            .unwrap();
+
+        assert_eq!(header, "token ghp_supersecret");
+        assert!(header.is_sensitive());
+    }
+
+    #[test]
+    fn authorization_header_fails_for_invalid_characters() {
+        assert!(SecretString::from("ghp_super\nsecret")
+            .authorization_header()
+            .is_err());
+    }
+}
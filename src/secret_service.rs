@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use zbus::{
+    blocking::{Connection, Proxy},
+    zvariant::{OwnedObjectPath, OwnedValue, Type, Value},
+};
+use zeroize::Zeroize;
+
+use crate::{host::Host, SecretString};
+
+const DESTINATION: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+
+// Any of these just mean falling back to gh, so there's no need to keep the details.
+#[derive(Debug)]
+pub(crate) enum SecretServiceError {
+    DBus,
+    Locked,
+    SecretNotUTF8,
+}
+
+impl From<zbus::Error> for SecretServiceError {
+    fn from(_: zbus::Error) -> Self {
+        Self::DBus
+    }
+}
+
+// The Secret struct from the Secret Service API, (oayays) on the wire. The value is
+// wiped on drop, and there's no Debug so that it can't be printed.
+#[derive(Serialize, Deserialize, Type)]
+struct Secret {
+    session: OwnedObjectPath,
+    parameters: Vec<u8>,
+    value: Vec<u8>,
+    content_type: String,
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+pub(crate) fn token_from_secret_service(
+    host: &Host,
+    user: Option<&str>,
+) -> Result<Option<SecretString>, SecretServiceError> {
+    lookup(&Connection::session()?, host, user)
+}
+
+// gh stores tokens via go-keyring, which keys items on a "gh:<host>" service attribute
+// and a "username" attribute that is empty for the active account.
+fn lookup(
+    connection: &Connection,
+    host: &Host,
+    user: Option<&str>,
+) -> Result<Option<SecretString>, SecretServiceError> {
+    let service = Proxy::new(connection, DESTINATION, SERVICE_PATH, SERVICE_INTERFACE)?;
+
+    let attributes = HashMap::from([
+        ("service", format!("gh:{host}")),
+        ("username", user.unwrap_or_default().to_owned()),
+    ]);
+    let (unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) =
+        service.call("SearchItems", &(attributes,))?;
+
+    let Some(item) = unlocked.into_iter().next() else {
+        // Unlocking may need to prompt the user, which is best left to gh.
+        return if locked.is_empty() {
+            Ok(None)
+        } else {
+            Err(SecretServiceError::Locked)
+        };
+    };
+
+    // The plain algorithm sends the secret unencrypted, which is fine over a local bus.
+    let (_, session): (OwnedValue, OwnedObjectPath) =
+        service.call("OpenSession", &("plain", Value::from("")))?;
+
+    let secret: Secret = Proxy::new(connection, DESTINATION, item, ITEM_INTERFACE)?
+        .call("GetSecret", &(session,))?;
+
+    std::str::from_utf8(&secret.value)
+        .map(|value| Some(value.into()))
+        .map_err(|_| SecretServiceError::SecretNotUTF8)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::net::UnixStream;
+
+    use zbus::{blocking::connection::Builder, fdo, zvariant::ObjectPath};
+
+    use super::*;
+
+    // Just enough of a Secret Service daemon to answer the calls lookup makes.
+    struct MockService {
+        items: Vec<(HashMap<String, String>, OwnedObjectPath, bool)>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Service")]
+    impl MockService {
+        fn open_session(
+            &self,
+            algorithm: &str,
+            _input: Value<'_>,
+        ) -> fdo::Result<(OwnedValue, OwnedObjectPath)> {
+            if algorithm != "plain" {
+                return Err(fdo::Error::NotSupported(algorithm.to_owned()));
+            }
+
+            Ok((
+                OwnedValue::try_from(Value::from("")).unwrap(),
+                ObjectPath::from_static_str_unchecked("/org/freedesktop/secrets/session/1").into(),
+            ))
+        }
+
+        fn search_items(
+            &self,
+            attributes: HashMap<String, String>,
+        ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+            let matching = self
+                .items
+                .iter()
+                .filter(|(item_attributes, _, _)| *item_attributes == attributes);
+
+            (
+                matching
+                    .clone()
+                    .filter(|(_, _, locked)| !locked)
+                    .map(|(_, path, _)| path.clone())
+                    .collect(),
+                matching
+                    .filter(|(_, _, locked)| *locked)
+                    .map(|(_, path, _)| path.clone())
+                    .collect(),
+            )
+        }
+    }
+
+    struct MockItem {
+        value: Vec<u8>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Secret.Item")]
+    impl MockItem {
+        fn get_secret(&self, session: OwnedObjectPath) -> Secret {
+            Secret {
+                session,
+                parameters: Vec::new(),
+                value: self.value.clone(),
+                content_type: "text/plain".to_owned(),
+            }
+        }
+    }
+
+    fn item(
+        service: &str,
+        username: &str,
+        path: &'static str,
+        locked: bool,
+    ) -> (HashMap<String, String>, OwnedObjectPath, bool) {
+        (
+            HashMap::from([
+                ("service".to_owned(), service.to_owned()),
+                ("username".to_owned(), username.to_owned()),
+            ]),
+            ObjectPath::from_static_str_unchecked(path).into(),
+            locked,
+        )
+    }
+
+    // Serves the mock daemon over one end of a socket pair, returning a client connection
+    // on the other so that no session bus is needed.
+    fn mock_daemon() -> (Connection, Connection) {
+        let (client, server) = UnixStream::pair().unwrap();
+
+        let server = std::thread::spawn(move || {
+            Builder::async_io_unix_stream(server)
+                .server(zbus::Guid::generate())
+                .unwrap()
+                .p2p()
+                .serve_at(
+                    SERVICE_PATH,
+                    MockService {
+                        items: vec![
+                            item(
+                                "gh:github.com",
+                                "",
+                                "/org/freedesktop/secrets/collection/login/1",
+                                false,
+                            ),
+                            item(
+                                "gh:github.com",
+                                "hubot",
+                                "/org/freedesktop/secrets/collection/login/2",
+                                false,
+                            ),
+                            item(
+                                "gh:my.ghes.com",
+                                "",
+                                "/org/freedesktop/secrets/collection/login/3",
+                                true,
+                            ),
+                        ],
+                    },
+                )
+                .unwrap()
+                .serve_at(
+                    "/org/freedesktop/secrets/collection/login/1",
+                    MockItem {
+                        value: b"active-token-value".to_vec(),
+                    },
+                )
+                .unwrap()
+                .serve_at(
+                    "/org/freedesktop/secrets/collection/login/2",
+                    MockItem {
+                        value: b"hubot-token-value".to_vec(),
+                    },
+                )
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+
+        let client = Builder::async_io_unix_stream(client).p2p().build().unwrap();
+        (client, server.join().unwrap())
+    }
+
+    #[test]
+    fn lookup_reads_active_token_for_host() {
+        let (client, _server) = mock_daemon();
+
+        assert_eq!(
+            lookup(&client, &Host::github(), None).unwrap(),
+            Some("active-token-value".into())
+        );
+    }
+
+    #[test]
+    fn lookup_reads_token_for_user() {
+        let (client, _server) = mock_daemon();
+
+        assert_eq!(
+            lookup(&client, &Host::github(), Some("hubot")).unwrap(),
+            Some("hubot-token-value".into())
+        );
+    }
+
+    #[test]
+    fn lookup_returns_none_when_no_item_matches() {
+        let (client, _server) = mock_daemon();
+
+        assert_eq!(
+            lookup(&client, &"tenant.ghe.com".parse().unwrap(), None).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn lookup_fails_when_item_is_locked() {
+        let (client, _server) = mock_daemon();
+
+        assert!(matches!(
+            lookup(&client, &"my.ghes.com".parse().unwrap(), None),
+            Err(SecretServiceError::Locked)
+        ));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{Source, Token, Var};
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum TokenKind {
+    Classic,               // ghp_
+    FineGrained,           // github_pat_
+    OAuthApp,              // gho_
+    GitHubAppUser,         // ghu_
+    GitHubAppInstallation, // ghs_
+    GitHubAppRefresh,      // ghr_
+    Actions,               // GITHUB_TOKEN in a GitHub Actions workflow
+    Legacy,                // 40 hex characters, either a classic PAT or an OAuth token
+    Unknown,
+}
+
+const PREFIXES: [(&str, TokenKind); 6] = [
+    ("ghp_", TokenKind::Classic),
+    ("github_pat_", TokenKind::FineGrained),
+    ("gho_", TokenKind::OAuthApp),
+    ("ghu_", TokenKind::GitHubAppUser),
+    ("ghs_", TokenKind::GitHubAppInstallation),
+    ("ghr_", TokenKind::GitHubAppRefresh),
+];
+
+impl Token {
+    // Classifies the token from its prefix, without asking the API.
+    pub fn kind(&self) -> TokenKind {
+        match (kind_from_prefix(self.value.expose()), &self.source) {
+            (TokenKind::GitHubAppInstallation, Source::Env(Var::GitHubToken))
+                if std::env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true") =>
+            {
+                TokenKind::Actions
+            }
+            (kind, _) => kind,
+        }
+    }
+}
+
+fn kind_from_prefix(value: &str) -> TokenKind {
+    if let Some((_, kind)) = PREFIXES
+        .iter()
+        .find(|(prefix, _)| value.starts_with(prefix))
+    {
+        return *kind;
+    }
+
+    if value.len() == 40 && value.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return TokenKind::Legacy;
+    }
+
+    TokenKind::Unknown
+}
+
+// Keeps the prefix, which says what kind of token it is, and hides the secret part.
+pub(crate) fn mask(value: &str) -> String {
+    let prefix = PREFIXES
+        .iter()
+        .find(|(prefix, _)| value.starts_with(prefix))
+        .map_or("", |(prefix, _)| prefix);
+
+    format!("{prefix}***")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_classifies_tokens_by_prefix() {
+        for (value, kind) in [
+            ("ghp_xxxx", TokenKind::Classic),
+            ("github_pat_xxxx", TokenKind::FineGrained),
+            ("gho_xxxx", TokenKind::OAuthApp),
+            ("ghu_xxxx", TokenKind::GitHubAppUser),
+            ("ghs_xxxx", TokenKind::GitHubAppInstallation),
+            ("ghr_xxxx", TokenKind::GitHubAppRefresh),
+            (
+                "0123456789abcdef0123456789ABCDEF01234567",
+                TokenKind::Legacy,
+            ),
+            ("0123456789abcdef", TokenKind::Unknown),
+            ("not-a-token", TokenKind::Unknown),
+        ] {
+            assert_eq!(Token::new(value, Source::Keyring).kind(), kind, "{value}");
+        }
+    }
+
+    #[test]
+    fn kind_detects_github_token_in_actions() {
+        temp_env::with_var("GITHUB_ACTIONS", Some("true"), || {
+            assert_eq!(
+                Token::new("ghs_xxxx", Source::Env(Var::GitHubToken)).kind(),
+                TokenKind::Actions
+            );
+            assert_eq!(
+                Token::new("ghs_xxxx", Source::Env(Var::GHToken)).kind(),
+                TokenKind::GitHubAppInstallation
+            );
+        });
+    }
+
+    #[test]
+    fn token_debug_and_display_mask_the_value() {
+        let token = Token::new("ghp_supersecret", Source::Keyring);
+
+        assert_eq!(token.to_string(), "ghp_***");
+        assert!(!format!("{token:?}").contains("supersecret"));
+        assert!(format!("{token:?}").contains("ghp_***"));
+        assert_eq!(mask("0123456789abcdef0123456789abcdef01234567"), "***");
+    }
+}
@@ -0,0 +1,290 @@
+use reqwest::{header::HeaderMap, Method, StatusCode};
+use serde::Deserialize;
+
+use crate::{ClientError, ClientOptions, RestClient, Token, TokenKind};
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub kind: TokenKind,
+    pub login: Option<String>, // installation tokens don't belong to a user
+    pub scopes: Option<Vec<String>>, // only tokens using OAuth scopes report them
+    pub expires_at: Option<String>, // e.g. "2024-03-01 00:00:00 UTC"
+}
+
+impl TokenInfo {
+    // Tokens without OAuth scopes, e.g. fine-grained PATs, are assumed to have any scope
+    // since their permissions can't be checked this way.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        let Some(scopes) = &self.scopes else {
+            return true;
+        };
+
+        scopes
+            .iter()
+            .any(|granted| granted == scope || implies(granted, scope))
+    }
+
+    pub fn missing_scopes<'a>(&self, required: &[&'a str]) -> Vec<&'a str> {
+        required
+            .iter()
+            .copied()
+            .filter(|scope| !self.has_scope(scope))
+            .collect()
+    }
+}
+
+// Whether a granted scope covers a narrower one, e.g. admin:org covers read:org. Mirrors
+// the scopes gh's api.ScopesSuggestion expands each granted scope to.
+pub(crate) fn implies(granted: &str, scope: &str) -> bool {
+    match granted {
+        "repo" => matches!(
+            scope,
+            "repo:status" | "repo_deployment" | "public_repo" | "repo:invite" | "security_events"
+        ),
+        "user" => matches!(scope, "read:user" | "user:email" | "user:follow"),
+        "codespace" => scope == "codespace:secrets",
+        _ => match (granted.split_once(':'), scope.split_once(':')) {
+            (Some(("admin", granted)), Some(("read" | "write", scope))) => granted == scope,
+            (Some(("write", granted)), Some(("read", scope))) => granted == scope,
+            _ => false,
+        },
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ValidateError {
+    Unauthorized, // the token is invalid, expired or revoked
+    Client(ClientError),
+}
+
+impl std::fmt::Display for ValidateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "token is invalid, try running `gh auth login`"),
+            Self::Client(err) => write!(f, "failed to validate token: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ValidateError {}
+
+impl From<ClientError> for ValidateError {
+    fn from(err: ClientError) -> Self {
+        match err {
+            ClientError::Http(err) if err.status == StatusCode::UNAUTHORIZED.as_u16() => {
+                Self::Unauthorized
+            }
+            err => Self::Client(err),
+        }
+    }
+}
+
+impl Token {
+    // Asks the host's API who the token belongs to and what it can do.
+    pub fn validate(&self, host: &str) -> Result<TokenInfo, ValidateError> {
+        validate_with(self, ClientOptions::new(host))
+    }
+}
+
+fn validate_with(token: &Token, options: ClientOptions) -> Result<TokenInfo, ValidateError> {
+    let kind = token.kind();
+
+    // /user turns away installation tokens, as there's no user behind them.
+    let has_user = !matches!(kind, TokenKind::GitHubAppInstallation | TokenKind::Actions);
+    let path = if has_user {
+        "user"
+    } else {
+        "installation/repositories?per_page=1"
+    };
+
+    let client = RestClient::with_options(options.auth_token(token.value.clone()))?;
+    let response = client.send(Method::GET, path, None::<&()>)?;
+
+    let scopes = header(&response.headers, "X-OAuth-Scopes").map(|scopes| {
+        scopes
+            .split(',')
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_owned)
+            .collect()
+    });
+
+    let kind = match kind {
+        TokenKind::Legacy | TokenKind::Unknown
+            if header(&response.headers, "X-OAuth-Client-Id").is_some() =>
+        {
+            TokenKind::OAuthApp
+        }
+        TokenKind::Legacy | TokenKind::Unknown if scopes.is_some() => TokenKind::Classic,
+        kind => kind,
+    };
+
+    #[derive(Deserialize)]
+    struct User {
+        login: String,
+    }
+
+    let login = if has_user {
+        Some(response.json::<User>()?.login)
+    } else {
+        None
+    };
+
+    Ok(TokenInfo {
+        kind,
+        login,
+        scopes,
+        expires_at: header(&response.headers, "GitHub-Authentication-Token-Expiration"),
+    })
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Source, Var};
+
+    fn options(server: &mockito::Server) -> ClientOptions {
+        ClientOptions::new("github.com").base_url(&server.url())
+    }
+
+    #[test]
+    fn validate_reports_login_scopes_and_expiry() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/user")
+            .match_header("authorization", "token ghp_xxxx")
+            .match_header("accept", "application/vnd.github+json")
+            .match_header("x-github-api-version", "2022-11-28")
+            .with_header("X-OAuth-Scopes", "repo, admin:org, gist")
+            .with_header(
+                "GitHub-Authentication-Token-Expiration",
+                "2024-03-01 00:00:00 UTC",
+            )
+            .with_body(r#"{"login":"monalisa"}"#)
+            .create();
+
+        let info = validate_with(&Token::new("ghp_xxxx", Source::Keyring), options(&server));
+
+        mock.assert();
+        assert_eq!(
+            info,
+            Ok(TokenInfo {
+                kind: TokenKind::Classic,
+                login: Some("monalisa".to_owned()),
+                scopes: Some(vec![
+                    "repo".to_owned(),
+                    "admin:org".to_owned(),
+                    "gist".to_owned()
+                ]),
+                expires_at: Some("2024-03-01 00:00:00 UTC".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn validate_detects_oauth_app_tokens_without_a_prefix() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/user")
+            .with_header("X-OAuth-Scopes", "")
+            .with_header("X-OAuth-Client-Id", "178c6fc778ccc68e1d6a")
+            .with_body(r#"{"login":"monalisa"}"#)
+            .create();
+
+        let info = validate_with(
+            &Token::new("0123456789abcdef0123456789abcdef01234567", Source::Keyring),
+            options(&server),
+        )
+        .unwrap();
+
+        assert_eq!(info.kind, TokenKind::OAuthApp);
+        assert_eq!(info.scopes, Some(vec![]));
+    }
+
+    #[test]
+    fn validate_checks_actions_tokens_against_installation() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/installation/repositories?per_page=1")
+            .with_body(r#"{"total_count":1,"repositories":[]}"#)
+            .create();
+
+        temp_env::with_var("GITHUB_ACTIONS", Some("true"), || {
+            assert_eq!(
+                validate_with(
+                    &Token::new("ghs_xxxx", Source::Env(Var::GitHubToken)),
+                    options(&server)
+                ),
+                Ok(TokenInfo {
+                    kind: TokenKind::Actions,
+                    login: None,
+                    scopes: None,
+                    expires_at: None,
+                })
+            )
+        });
+        mock.assert();
+    }
+
+    #[test]
+    fn validate_fails_for_rejected_tokens() {
+        let mut server = mockito::Server::new();
+        server.mock("GET", "/user").with_status(401).create();
+
+        assert_eq!(
+            validate_with(&Token::new("ghp_xxxx", Source::Keyring), options(&server)),
+            Err(ValidateError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn missing_scopes_accounts_for_broader_scopes() {
+        let info = TokenInfo {
+            kind: TokenKind::Classic,
+            login: Some("monalisa".to_owned()),
+            scopes: Some(vec!["repo".to_owned(), "admin:org".to_owned()]),
+            expires_at: None,
+        };
+
+        assert_eq!(
+            info.missing_scopes(&["read:org", "repo:status", "public_repo", "gist"]),
+            vec!["gist"]
+        );
+        assert_eq!(
+            info.missing_scopes(&["repo_deployment", "repo:invite", "security_events"]),
+            Vec::<&str>::new()
+        );
+        assert_eq!(
+            info.missing_scopes(&["read:user", "codespace:secrets"]),
+            vec!["read:user", "codespace:secrets"]
+        );
+
+        let info = TokenInfo {
+            scopes: Some(vec!["user".to_owned(), "codespace".to_owned()]),
+            ..info
+        };
+        assert_eq!(
+            info.missing_scopes(&["read:user", "user:email", "codespace:secrets", "repo"]),
+            vec!["repo"]
+        );
+    }
+
+    #[test]
+    fn missing_scopes_is_empty_without_oauth_scopes() {
+        let info = TokenInfo {
+            kind: TokenKind::FineGrained,
+            login: Some("monalisa".to_owned()),
+            scopes: None,
+            expires_at: None,
+        };
+
+        assert!(info.missing_scopes(&["read:org"]).is_empty());
+    }
+}